@@ -8,6 +8,27 @@ pub enum PacketsError {
     UnknownValueType(u8),
     #[error("Unknown instruction type: {0}")]
     UnknownInstructionType(u8),
+    #[error("Score is not a number.")]
+    InvalidScore,
+    #[error("Length {length} exceeds the limit of {limit}.")]
+    LengthExceeded { length: usize, limit: usize },
+    #[error("Value nests deeper than the limit of {limit}.")]
+    NestingTooDeep { limit: usize },
+    #[error("Peer did not start with a handshake.")]
+    HandshakeExpected,
+    #[error("Protocol version {remote} is not supported, expected {local}.")]
+    VersionMismatch { local: u16, remote: u16 },
+    #[error("Peer is missing required capabilities: {0:#x}")]
+    MissingCapabilities(u32),
+    #[error("Malformed RESP request: {0}")]
+    MalformedResp(&'static str),
+    #[error("Could not convert value: {0}")]
+    Serde(String),
+    #[error("Expected {expected}, found {found}.")]
+    UnexpectedType {
+        expected: &'static str,
+        found: &'static str,
+    },
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -18,6 +39,16 @@ pub enum StateError {
     CannotReturnKeyType,
     #[error("Attempted to index a key with a bad type.")]
     BadKeyType,
+    #[error("Mismatched number of keys and values.")]
+    MismatchedArguments,
+    #[error("Value would overflow.")]
+    Overflow,
+    #[error("Resulting score would not be a number.")]
+    InvalidScore,
+    #[error("Index out of range.")]
+    IndexOutOfRange,
+    #[error("Path cannot be used with this command.")]
+    InvalidPath,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -32,6 +63,8 @@ pub enum InfernoError {
     FromUtf8(#[from] FromUtf8Error),
     #[error("{0}")]
     DecodedMessage(String),
+    #[error("Connection closed before a response was received.")]
+    ConnectionClosed,
 }
 
 pub type Result<T> = std::result::Result<T, InfernoError>;
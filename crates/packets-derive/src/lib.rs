@@ -0,0 +1,254 @@
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote, quote_spanned};
+use std::collections::HashMap;
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, parse_quote, Attribute, Data, DeriveInput, Fields, Ident, LitInt, LitStr,
+    Path,
+};
+
+/// Derives `packets::Packet`, writing fields in declaration order.
+///
+/// Enum variants are prefixed with the `u8` given by `#[packet(tag = ...)]`. Fields accept
+/// `#[packet(skip)]`, read back as `Default::default()`, and `#[packet(with = "module")]`,
+/// which calls `module::write(&value, stream)` and `module::read(stream)` instead.
+#[proc_macro_derive(Packet, attributes(packet))]
+pub fn derive_packet(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(mut input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+
+    let type_params: Vec<Ident> = input
+        .generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect();
+    let where_clause = input.generics.make_where_clause();
+    for param in type_params {
+        where_clause
+            .predicates
+            .push(parse_quote!(#param: ::packets::Packet));
+    }
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let (write, read) = match &input.data {
+        Data::Struct(data) => {
+            let codec = FieldsCodec::new(&data.fields)?;
+            let pattern = &codec.pattern;
+            let writes = &codec.writes;
+            let reads = &codec.reads;
+            let construct = &codec.construct;
+            (
+                quote! {
+                    let Self #pattern = self;
+                    #(#writes)*
+                },
+                quote! {
+                    #(#reads)*
+                    Ok(Self #construct)
+                },
+            )
+        }
+        Data::Enum(data) => {
+            let mut tags = HashMap::new();
+            let mut write_arms = Vec::new();
+            let mut read_arms = Vec::new();
+            for variant in &data.variants {
+                let tag = variant_tag(&variant.attrs, variant.ident.span())?;
+                if let Some(previous) = tags.insert(tag, &variant.ident) {
+                    return Err(syn::Error::new(
+                        variant.ident.span(),
+                        format!("tag {} is already used by `{}`", tag, previous),
+                    ));
+                }
+
+                let ident = &variant.ident;
+                let codec = FieldsCodec::new(&variant.fields)?;
+                let pattern = &codec.pattern;
+                let writes = &codec.writes;
+                let reads = &codec.reads;
+                let construct = &codec.construct;
+                write_arms.push(quote! {
+                    Self::#ident #pattern => {
+                        ::packets::__private::AsyncWriteExt::write_u8(stream, #tag).await?;
+                        #(#writes)*
+                    }
+                });
+                read_arms.push(quote! {
+                    #tag => {
+                        #(#reads)*
+                        Ok(Self::#ident #construct)
+                    }
+                });
+            }
+            (
+                quote! {
+                    match self {
+                        #(#write_arms)*
+                    }
+                },
+                quote! {
+                    let tag = ::packets::__private::AsyncReadExt::read_u8(stream).await?;
+                    match tag {
+                        #(#read_arms)*
+                        _ => Err(::packets::__private::errors::InfernoError::Packets(
+                            ::packets::__private::errors::PacketsError::UnknownPacketType(tag),
+                        )),
+                    }
+                },
+            )
+        }
+        Data::Union(data) => {
+            return Err(syn::Error::new(
+                data.union_token.span,
+                "Packet cannot be derived for unions",
+            ))
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::packets::Packet for #name #ty_generics #where_clause {
+            async fn write<__W>(&self, stream: &mut __W) -> ::packets::__private::errors::Result<()>
+            where
+                __W: ::packets::__private::AsyncWrite + Unpin,
+            {
+                #write
+                Ok(())
+            }
+
+            async fn read<__R>(stream: &mut __R) -> ::packets::__private::errors::Result<Self>
+            where
+                __R: ::packets::__private::AsyncRead + Unpin,
+            {
+                #read
+            }
+        }
+    })
+}
+
+/// Pieces needed to write and read one set of fields, shared by structs and variants.
+struct FieldsCodec {
+    pattern: TokenStream2,
+    writes: Vec<TokenStream2>,
+    reads: Vec<TokenStream2>,
+    construct: TokenStream2,
+}
+
+impl FieldsCodec {
+    fn new(fields: &Fields) -> syn::Result<Self> {
+        let mut patterns = Vec::new();
+        let mut writes = Vec::new();
+        let mut reads = Vec::new();
+        let mut bindings = Vec::new();
+
+        for (index, field) in fields.iter().enumerate() {
+            let attrs = FieldAttrs::parse(&field.attrs)?;
+            let binding = match &field.ident {
+                Some(ident) => format_ident!("__{}", ident),
+                None => format_ident!("__field{}", index),
+            };
+            let ty = &field.ty;
+            let span = field.ty.span();
+
+            let bound = if attrs.skip {
+                quote!(_)
+            } else {
+                quote!(#binding)
+            };
+            patterns.push(match &field.ident {
+                Some(ident) => quote!(#ident: #bound),
+                None => bound,
+            });
+
+            if attrs.skip {
+                reads.push(quote_spanned! {span=>
+                    let #binding: #ty = ::core::default::Default::default();
+                });
+            } else if let Some(with) = &attrs.with {
+                writes.push(quote_spanned! {span=> #with::write(#binding, stream).await?; });
+                reads
+                    .push(quote_spanned! {span=> let #binding: #ty = #with::read(stream).await?; });
+            } else {
+                writes.push(
+                    quote_spanned! {span=> ::packets::Packet::write(#binding, stream).await?; },
+                );
+                reads.push(quote_spanned! {span=>
+                    let #binding = <#ty as ::packets::Packet>::read(stream).await?;
+                });
+            }
+
+            bindings.push(match &field.ident {
+                Some(ident) => quote!(#ident: #binding),
+                None => quote!(#binding),
+            });
+        }
+
+        let (pattern, construct) = match fields {
+            Fields::Named(_) => (quote!({ #(#patterns),* }), quote!({ #(#bindings),* })),
+            Fields::Unnamed(_) => (quote!(( #(#patterns),* )), quote!(( #(#bindings),* ))),
+            Fields::Unit => (quote!(), quote!()),
+        };
+
+        Ok(Self {
+            pattern,
+            writes,
+            reads,
+            construct,
+        })
+    }
+}
+
+#[derive(Default)]
+struct FieldAttrs {
+    skip: bool,
+    with: Option<Path>,
+}
+
+impl FieldAttrs {
+    fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut parsed = Self::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("packet")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("skip") {
+                    parsed.skip = true;
+                    Ok(())
+                } else if meta.path.is_ident("with") {
+                    let module: LitStr = meta.value()?.parse()?;
+                    parsed.with = Some(module.parse()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `skip` or `with`"))
+                }
+            })?;
+        }
+        if parsed.skip && parsed.with.is_some() {
+            return Err(syn::Error::new(
+                attrs[0].span(),
+                "`skip` and `with` cannot be combined",
+            ));
+        }
+        Ok(parsed)
+    }
+}
+
+fn variant_tag(attrs: &[Attribute], span: Span) -> syn::Result<u8> {
+    let mut tag = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("packet")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("tag") {
+                let value: LitInt = meta.value()?.parse()?;
+                tag = Some(value.base10_parse::<u8>()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `tag`"))
+            }
+        })?;
+    }
+    tag.ok_or_else(|| syn::Error::new(span, "enum variants need #[packet(tag = ...)]"))
+}
@@ -0,0 +1,29 @@
+use crate::Packet;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+/// Id chosen by the client for a request, the response to it carries the same id.
+pub type RequestId = u32;
+
+/// A packet tagged with the id of the request it belongs to.
+#[derive(Debug, Packet)]
+pub struct Envelope<T> {
+    pub id: RequestId,
+    pub packet: T,
+}
+
+impl<T> Envelope<T> {
+    pub fn new(id: RequestId, packet: T) -> Self {
+        Self { id, packet }
+    }
+}
+
+impl<T: Packet> Envelope<T> {
+    /// Writes `packet` tagged with `id` without taking ownership of it.
+    pub async fn write_tagged<W>(id: RequestId, packet: &T, stream: &mut W) -> errors::Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        stream.write_u32(id).await?;
+        packet.write(stream).await
+    }
+}
@@ -1,7 +1,10 @@
+use crate::frame::{check_length, read_exact_bytes, MAX_BYTES_LENGTH, MAX_COLLECTION_LENGTH};
 use crate::{Packet, PacketDelegate};
 use errors::InfernoError;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
+pub(crate) const PREALLOCATE_LIMIT: usize = 1024;
+
 impl<K, V> Packet for (K, V)
 where
     K: Packet,
@@ -42,6 +45,42 @@ impl Packet for u32 {
     }
 }
 
+impl Packet for i32 {
+    async fn write<W>(&self, stream: &mut W) -> errors::Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        stream.write_i32(*self).await?;
+        Ok(())
+    }
+
+    async fn read<R>(stream: &mut R) -> errors::Result<Self>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let value = stream.read_i32().await?;
+        Ok(value)
+    }
+}
+
+impl Packet for i64 {
+    async fn write<W>(&self, stream: &mut W) -> errors::Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        stream.write_i64(*self).await?;
+        Ok(())
+    }
+
+    async fn read<R>(stream: &mut R) -> errors::Result<Self>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let value = stream.read_i64().await?;
+        Ok(value)
+    }
+}
+
 impl Packet for String {
     async fn write<W>(&self, stream: &mut W) -> errors::Result<()>
     where
@@ -56,9 +95,8 @@ impl Packet for String {
     where
         R: AsyncRead + Unpin,
     {
-        let length = stream.read_u32().await?;
-        let mut buf = vec![0u8; length as usize];
-        stream.read_exact(&mut buf).await?;
+        let length = check_length(stream.read_u32().await?, MAX_BYTES_LENGTH)?;
+        let buf = read_exact_bytes(stream, length).await?;
         Ok(String::from_utf8(buf)?)
     }
 }
@@ -91,8 +129,9 @@ impl<T: Packet> Packet for Vec<T> {
     where
         R: AsyncRead + Unpin,
     {
-        let length = stream.read_u32().await?;
-        let mut packets = Vec::with_capacity(length as usize);
+        let length = check_length(stream.read_u32().await?, MAX_COLLECTION_LENGTH)?;
+        // the length is only a claim, grow as elements actually arrive
+        let mut packets = Vec::with_capacity(length.min(PREALLOCATE_LIMIT));
         for _ in 0..length {
             packets.push(<T as Packet>::read(stream).await?);
         }
@@ -0,0 +1,76 @@
+use crate::Packet;
+use errors::{PacketsError, Result};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Largest frame accepted unless configured otherwise, 16 MiB.
+pub const DEFAULT_MAX_FRAME_SIZE: u32 = 16 * 1024 * 1024;
+/// Most elements a single list, map or collection argument may hold.
+pub const MAX_COLLECTION_LENGTH: u32 = 1024 * 1024;
+/// Most bytes a single string, key or blob may hold.
+pub const MAX_BYTES_LENGTH: u32 = DEFAULT_MAX_FRAME_SIZE;
+/// Most lists and maps a value may nest inside each other.
+pub const MAX_NESTING_DEPTH: usize = 64;
+
+/// Rejects `length` when it is over `limit`, before anything is allocated for it.
+pub(crate) fn check_length(length: u32, limit: u32) -> Result<usize> {
+    if length > limit {
+        return Err(PacketsError::LengthExceeded {
+            length: length as usize,
+            limit: limit as usize,
+        }
+        .into());
+    }
+    Ok(length as usize)
+}
+
+/// Reads exactly `length` bytes, the buffer only grows as bytes actually arrive.
+pub(crate) async fn read_exact_bytes<R>(stream: &mut R, length: usize) -> Result<Vec<u8>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut buf = Vec::new();
+    stream.take(length as u64).read_to_end(&mut buf).await?;
+    if buf.len() < length {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(buf)
+}
+
+/// Writes `payload` as one frame, prefixed with its length.
+pub async fn write_frame<W>(stream: &mut W, payload: &[u8]) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let length = u32::try_from(payload.len()).map_err(|_| PacketsError::LengthExceeded {
+        length: payload.len(),
+        limit: u32::MAX as usize,
+    })?;
+    // one write, a lone length prefix would wait on the peer's delayed ack
+    let mut frame = Vec::with_capacity(4 + payload.len());
+    frame.extend_from_slice(&length.to_be_bytes());
+    frame.extend_from_slice(payload);
+    stream.write_all(&frame).await?;
+    Ok(())
+}
+
+/// Writes `packet` as one frame.
+pub async fn write_packet<T, W>(stream: &mut W, packet: &T) -> Result<()>
+where
+    T: Packet,
+    W: AsyncWrite + Unpin,
+{
+    let mut payload = Vec::new();
+    packet.write(&mut payload).await?;
+    write_frame(stream, &payload).await
+}
+
+/// Reads one frame of at most `max_frame_size` bytes and decodes a packet from it.
+pub async fn read_packet<T, R>(stream: &mut R, max_frame_size: u32) -> Result<T>
+where
+    T: Packet,
+    R: AsyncRead + Unpin,
+{
+    let length = check_length(stream.read_u32().await?, max_frame_size)?;
+    let payload = read_exact_bytes(stream, length).await?;
+    T::read(&mut payload.as_slice()).await
+}
@@ -0,0 +1,136 @@
+use crate::Packet;
+use errors::{PacketsError, Result};
+use std::fmt;
+use std::ops::BitOr;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Bumped whenever the wire format changes incompatibly.
+pub const PROTOCOL_VERSION: u16 = 1;
+
+// lets a peer speaking something else fail the handshake instead of sending nonsense commands
+const MAGIC: [u8; 4] = *b"INFR";
+
+/// Optional protocol features a peer supports.
+#[derive(Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct Capabilities(u32);
+
+impl Capabilities {
+    pub const NONE: Self = Self(0);
+    pub const COMPRESSION: Self = Self(1 << 0);
+    pub const REQUEST_IDS: Self = Self(1 << 1);
+    pub const PUSH_MESSAGES: Self = Self(1 << 2);
+
+    /// Everything this build implements.
+    pub const SUPPORTED: Self = Self::REQUEST_IDS;
+    /// Everything a peer must support to talk to this build.
+    pub const REQUIRED: Self = Self::REQUEST_IDS;
+
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+}
+
+impl BitOr for Capabilities {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl fmt::Debug for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = [
+            (Self::COMPRESSION, "COMPRESSION"),
+            (Self::REQUEST_IDS, "REQUEST_IDS"),
+            (Self::PUSH_MESSAGES, "PUSH_MESSAGES"),
+        ];
+        f.debug_set()
+            .entries(
+                names
+                    .iter()
+                    .filter(|(flag, _)| self.contains(*flag))
+                    .map(|(_, name)| name),
+            )
+            .finish()
+    }
+}
+
+/// First packet in each direction, before any command is sent.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Hello {
+    pub version: u16,
+    pub capabilities: Capabilities,
+}
+
+impl Hello {
+    pub fn new(capabilities: Capabilities) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            capabilities,
+        }
+    }
+
+    /// Agrees on what both sides support, failing if the peer cannot be talked to.
+    pub fn negotiate(&self, remote: &Hello) -> Result<Hello> {
+        if self.version != remote.version {
+            return Err(PacketsError::VersionMismatch {
+                local: self.version,
+                remote: remote.version,
+            }
+            .into());
+        }
+        let capabilities = self.capabilities.intersection(remote.capabilities);
+        if !capabilities.contains(Capabilities::REQUIRED) {
+            return Err(PacketsError::MissingCapabilities(
+                Capabilities::REQUIRED.bits() & !capabilities.bits(),
+            )
+            .into());
+        }
+        Ok(Hello {
+            version: self.version,
+            capabilities,
+        })
+    }
+}
+
+impl Default for Hello {
+    fn default() -> Self {
+        Self::new(Capabilities::SUPPORTED)
+    }
+}
+
+impl Packet for Hello {
+    async fn write<W>(&self, stream: &mut W) -> Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        stream.write_all(&MAGIC).await?;
+        stream.write_u16(self.version).await?;
+        stream.write_u32(self.capabilities.0).await?;
+        Ok(())
+    }
+
+    async fn read<R>(stream: &mut R) -> Result<Self>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut magic = [0u8; 4];
+        stream.read_exact(&mut magic).await?;
+        if magic != MAGIC {
+            return Err(PacketsError::HandshakeExpected.into());
+        }
+        Ok(Self {
+            version: stream.read_u16().await?,
+            capabilities: Capabilities(stream.read_u32().await?),
+        })
+    }
+}
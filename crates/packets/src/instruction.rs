@@ -0,0 +1,66 @@
+use crate::Packet;
+use errors::InfernoError;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// One step of a path into a composite value, paths are followed left to right from the key.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Instruction {
+    /// Field of a hash.
+    Hash(String),
+    /// Position in a list, counted from the front.
+    Index(u32),
+    /// Member of a set.
+    Member(String),
+}
+
+impl Instruction {
+    pub fn hash(field: impl Into<String>) -> Self {
+        Self::Hash(field.into())
+    }
+
+    pub fn index(index: u32) -> Self {
+        Self::Index(index)
+    }
+
+    pub fn member(member: impl Into<String>) -> Self {
+        Self::Member(member.into())
+    }
+}
+
+impl Packet for Instruction {
+    async fn write<W>(&self, stream: &mut W) -> errors::Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        match self {
+            Instruction::Hash(field) => {
+                stream.write_u8(0).await?;
+                field.write(stream).await?;
+            }
+            Instruction::Index(index) => {
+                stream.write_u8(1).await?;
+                stream.write_u32(*index).await?;
+            }
+            Instruction::Member(member) => {
+                stream.write_u8(2).await?;
+                member.write(stream).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn read<R>(stream: &mut R) -> errors::Result<Self>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let instruction = stream.read_u8().await?;
+        match instruction {
+            0 => Ok(Instruction::Hash(String::read(stream).await?)),
+            1 => Ok(Instruction::Index(stream.read_u32().await?)),
+            2 => Ok(Instruction::Member(String::read(stream).await?)),
+            _ => Err(InfernoError::Packets(
+                errors::PacketsError::UnknownInstructionType(instruction),
+            )),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use crate::frame::{check_length, read_exact_bytes, MAX_BYTES_LENGTH};
+use crate::Packet;
+use std::borrow::Borrow;
+use std::fmt;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Key of a top level value, any sequence of bytes.
+#[derive(Clone, Default, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct Key(Vec<u8>);
+
+impl Key {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\"", self.0.escape_ascii())
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.escape_ascii())
+    }
+}
+
+impl From<&str> for Key {
+    fn from(value: &str) -> Self {
+        Self(value.as_bytes().to_vec())
+    }
+}
+
+impl From<String> for Key {
+    fn from(value: String) -> Self {
+        Self(value.into_bytes())
+    }
+}
+
+impl From<&[u8]> for Key {
+    fn from(value: &[u8]) -> Self {
+        Self(value.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for Key {
+    fn from(value: Vec<u8>) -> Self {
+        Self(value)
+    }
+}
+
+impl AsRef<[u8]> for Key {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Borrow<[u8]> for Key {
+    fn borrow(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Packet for Key {
+    async fn write<W>(&self, stream: &mut W) -> errors::Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        stream.write_u32(self.0.len() as u32).await?;
+        stream.write_all(&self.0).await?;
+        Ok(())
+    }
+
+    async fn read<R>(stream: &mut R) -> errors::Result<Self>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let length = check_length(stream.read_u32().await?, MAX_BYTES_LENGTH)?;
+        Ok(Self(read_exact_bytes(stream, length).await?))
+    }
+}
@@ -1,14 +1,34 @@
-#![feature(macro_metavar_expr)]
-
+pub mod envelope;
 pub mod ext;
-pub(crate) mod macros;
+pub mod frame;
+pub mod handshake;
+pub mod instruction;
+pub mod key;
+pub mod score;
+#[cfg(feature = "serde")]
+pub mod serde_value;
 pub mod value;
 
+// lets derived impls name `::packets` from inside this crate too
+extern crate self as packets;
+
+pub use packets_derive::Packet;
+
+// paths used by `#[derive(Packet)]`, not part of the public API
+#[doc(hidden)]
+pub mod __private {
+    pub use errors;
+    pub use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+}
+
+use crate::instruction::Instruction;
+use crate::key::Key;
+use crate::score::Score;
 use crate::value::ValueType;
 use errors::InfernoError;
 use errors::Result;
 use std::future::Future;
-use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncWrite};
 
 pub trait Packet: Sized {
     fn write<W>(&self, stream: &mut W) -> impl Future<Output = Result<()>>
@@ -46,200 +66,334 @@ impl<T: PacketDelegate> Packet for T {
 }
 
 macro_rules! packet_types {
-    (@executor $me:ident -> $executor:ident { $($name:ident as $fn_ident:ident $({
+    ($packet_enum:ident -> $executor:ident, $queue:ident { $($name:ident = $code:literal as $fn_ident:ident $({
         $($field:ident: $field_type:ty),*$(,)?
     })?),*$(,)? } -> $return_type:ty) => {
+        #[derive(Debug, Packet)]
+        pub enum $packet_enum {
+            $(
+                #[packet(tag = $code)]
+                $name$({
+                    $($field: $field_type),*
+                })?,
+            )*
+        }
+
         pub trait $executor: Sized {
             $(
                 fn $fn_ident(self$(, $($field: $field_type),*)?) -> impl Future<Output = Result<$return_type>>;
             )*
         }
 
-        impl<T> $executor for T where T: PacketSender<$me, $return_type> {
+        impl<T> $executor for T where T: PacketSender<$packet_enum, $return_type> {
             $(
                 async fn $fn_ident(self$(, $($field: $field_type),*)?) -> Result<$return_type> {
-                    self.send(&$me::$name$({
+                    self.send(&$packet_enum::$name$({
                         $($field),*
                     })?).await
                 }
             )*
         }
-    };
-    (@executor $me:ident { $($name:ident $({
-        $($field:ident: $field_type:ty),*$(,)?
-    })?),*$(,)? }) => {};
-    ($packet_enum:ident $(-> $executor:ident)? { $($name:ident $(as $fn_ident:ident)? $({
-        $($field:ident: $field_type:ty),*$(,)?
-    })?),*$(,)? } $(-> $return_type:ty)?) => {
-        #[derive(Debug)]
-        pub enum $packet_enum {
+
+        pub trait $queue: Sized {
             $(
-                $name$({
-                    $($field: $field_type),*
-                })?,
+                fn $fn_ident(&mut self$(, $($field: $field_type),*)?) -> &mut Self;
             )*
         }
 
-        packet_types!(@executor $packet_enum $(-> $executor)? { $($name $(as $fn_ident)? $({
-            $($field: $field_type),*
-        })?),* } $(-> $return_type)?);
-
-        impl Packet for $packet_enum {
-            async fn write<W>(&self, stream: &mut W) -> Result<()>
-            where
-                W: AsyncWrite + Unpin,
-            {
-                match self {
-                    $(
-                        $packet_enum::$name$({
-                            $($field),*
-                        })? => {
-                            stream.write_u8(${index()}).await?;
-                            $(
-                            $(
-                                $field.write(stream).await?;
-                            )*
-                            )?
-                            Ok(())
-                        },
-                    )*
-                }
-            }
-
-            async fn read<R>(stream: &mut R) -> Result<Self>
-            where
-                R: AsyncRead + Unpin,
-            {
-                let value = stream.read_u8().await?;
-                match value {
-                    $(
-                        ${index()} => {
-                            $(
-                                $(let $field = <$field_type as $crate::Packet>::read(stream).await?;)*
-                            )?
-                            Ok(
-                                Self::$name $({
-                                    $($field),*
-                                })?
-                            )
-                        },
-                    )*
-                    _ => Err(InfernoError::Packets(errors::PacketsError::UnknownPacketType(value))),
+        impl<T> $queue for T where T: PacketQueue<$packet_enum> {
+            $(
+                fn $fn_ident(&mut self$(, $($field: $field_type),*)?) -> &mut Self {
+                    self.queue($packet_enum::$name$({
+                        $($field),*
+                    })?);
+                    self
                 }
-            }
+            )*
         }
-    }
+    };
 }
 
-packet_types! {
-    ServerResponse {
-        Error { err: InfernoError },
-        Ok,
-        Single { value: ValueType },
-        Bulk { values: Vec<ValueType> },
-        OptInt { value: Option<u32> },
-        IntList { values: Vec<u32> },
-    }
+#[derive(Debug, Packet)]
+pub enum ServerResponse {
+    #[packet(tag = 0)]
+    Error { err: InfernoError },
+    #[packet(tag = 1)]
+    Ok,
+    #[packet(tag = 2)]
+    Single { value: ValueType },
+    #[packet(tag = 3)]
+    Bulk { values: Vec<ValueType> },
+    #[packet(tag = 4)]
+    OptInt { value: Option<u32> },
+    #[packet(tag = 5)]
+    IntList { values: Vec<u32> },
+    #[packet(tag = 6)]
+    OptScore { value: Option<Score> },
+    #[packet(tag = 7)]
+    Scores { values: Vec<Option<Score>> },
+    #[packet(tag = 8)]
+    ScoredBulk { values: Vec<(String, Score)> },
 }
 
 packet_types! {
-    ClientCommand -> ClientCommandExecutor {
+    ClientCommand -> ClientCommandExecutor, ClientCommandQueue {
         //// Arbitrary Commands ////
 
-        Expire as expire { key: String, expire: u32 },
-        Persist as persist { key: String },
-        Ttl as ttl { key: String },
-        Del as del { keys: Vec<String> },
+        Expire = 0 as expire { key: Key, expire: u32 },
+        Persist = 1 as persist { key: Key },
+        Ttl = 2 as ttl { key: Key },
+        Del = 3 as del { key: Key, path: Vec<Instruction> },
+        Ping = 81 as ping,
 
         //// Value Commands ////
 
-        Decr as decr { key: String },
-        DecrBy as decr_by { key: String, by: u32 },
-        Incr as incr { key: String },
-        IncrBy as incr_by { key: String, by: u32 },
+        Decr = 4 as decr { key: Key },
+        DecrBy = 5 as decr_by { key: Key, by: i64 },
+        Incr = 6 as incr { key: Key },
+        IncrBy = 7 as incr_by { key: Key, by: i64 },
 
-        Get as get { key: String },
-        GetDel as get_del { keys: Vec<String> },
-        GetEx as get_ex { key: String, expire: u32 },
-        GetSet as get_set { key: String, value: ValueType },
-        MGet as mget { keys: Vec<String> },
+        Get = 8 as get { key: Key, path: Vec<Instruction> },
+        GetDel = 9 as get_del { keys: Vec<Key> },
+        GetEx = 10 as get_ex { key: Key, expire: u32 },
+        GetSet = 11 as get_set { key: Key, value: ValueType },
+        MGet = 12 as mget { keys: Vec<Key> },
 
-        Set as set { key: String, value: ValueType },
-        SetEx as set_ex { key: String, value: ValueType, expire: u32 },
-        SetNx as set_nx { key: String, value: ValueType },
-        MSet as mset { keys: Vec<String>, values: Vec<ValueType> },
-        MSetNx as mset_nx { keys: Vec<String>, values: Vec<ValueType> },
+        Set = 13 as set { key: Key, path: Vec<Instruction>, value: ValueType },
+        SetEx = 14 as set_ex { key: Key, value: ValueType, expire: u32 },
+        SetNx = 15 as set_nx { key: Key, value: ValueType },
+        MSet = 16 as mset { keys: Vec<Key>, values: Vec<ValueType> },
+        MSetNx = 17 as mset_nx { keys: Vec<Key>, values: Vec<ValueType> },
 
         //// Hash Commands ////
 
-        HExpire as hexpire { key: String, field: String, expire: u32 },
-        HDel as hdel { key: String, fields: Vec<String> },
-        HDelGet as hdel_get { key: String, fields: Vec<String> },
-        HPopRand as hpop_rand { key: String, count: u32 },
-
-        HExists as hexists { key: String, field: String },
-        HGet as hget { key: String, field: String },
-        HGetAll as hget_all { key: String },
-        HMGet as hmget { key: String, fields: Vec<String> },
-        HKeys as hkeys { key: String },
-        HValues as hvalues { key: String },
-        HLen as hlen { key: String },
-
-        HDecr as hdecr { key: String, field: String },
-        HDecrBy as hdecr_by { key: String, field: String },
-        HIncr as hincr { key: String, field: String },
-        HIncrBy as hincr_by { key: String, field: String, by: u32 },
-
-        HSet as hset { key: String, field: String, value: ValueType },
-        HSetNx as hset_nx { key: String, field: String, value: ValueType },
-        HSetEx as hset_ex { key: String, field: String, value: ValueType, expire: u32 },
-        HMSet as hmset { key: String, fields: Vec<(String, ValueType)> },
-        HMSetNx as hmset_nx { key: String, fields: Vec<(String, ValueType)> },
-
-        //// Set Commands ////
-
-        ZAdd as zadd { key: String, score: u32, member: String },
-        ZAddNx as zadd_nx { key: String, score: u32, member: String },
-        ZIncrBy as zincr_by { key: String, score: u32, member: String },
-        ZDecrBy as zdecr_by { key: String, score: u32, member: String },
-
-        ZScore as zscore { key: String, member: String },
-        ZMScore as zmscore { key: String, members: Vec<String> },
-
-        ZPopMin as zpop_min { key: String, count: u32 },
-        ZPopMax as zpop_max { key: String, count: u32 },
-
-        ZRem as zrem { key: String, member: String },
-        ZExpire as zexpire { key: String, member: String, expire: u32 },
+        HExpire = 18 as hexpire { key: Key, field: String, expire: u32 },
+        HDel = 19 as hdel { key: Key, fields: Vec<String> },
+        HDelGet = 20 as hdel_get { key: Key, fields: Vec<String> },
+        HPopRand = 21 as hpop_rand { key: Key, count: u32 },
+
+        HExists = 22 as hexists { key: Key, field: String },
+        HGet = 23 as hget { key: Key, field: String },
+        HGetAll = 24 as hget_all { key: Key },
+        HMGet = 25 as hmget { key: Key, fields: Vec<String> },
+        HKeys = 26 as hkeys { key: Key },
+        HValues = 27 as hvalues { key: Key },
+        HLen = 28 as hlen { key: Key },
+
+        HDecr = 29 as hdecr { key: Key, field: String },
+        HDecrBy = 30 as hdecr_by { key: Key, field: String, by: i64 },
+        HIncr = 31 as hincr { key: Key, field: String },
+        HIncrBy = 32 as hincr_by { key: Key, field: String, by: i64 },
+
+        HSet = 33 as hset { key: Key, field: String, value: ValueType },
+        HSetNx = 34 as hset_nx { key: Key, field: String, value: ValueType },
+        HSetEx = 35 as hset_ex { key: Key, field: String, value: ValueType, expire: u32 },
+        HMSet = 36 as hmset { key: Key, fields: Vec<(String, ValueType)> },
+        HMSetNx = 37 as hmset_nx { key: Key, fields: Vec<(String, ValueType)> },
+
+        //// Sorted Set Commands ////
+
+        ZAdd = 38 as zadd { key: Key, score: Score, member: String },
+        ZAddNx = 39 as zadd_nx { key: Key, score: Score, member: String },
+        ZIncrBy = 40 as zincr_by { key: Key, by: Score, member: String },
+        ZDecrBy = 41 as zdecr_by { key: Key, by: Score, member: String },
+
+        ZScore = 42 as zscore { key: Key, member: String },
+        ZMScore = 43 as zmscore { key: Key, members: Vec<String> },
+        ZRange = 44 as zrange { key: Key, start: i32, stop: i32 },
+        ZRangeByScore = 45 as zrange_by_score { key: Key, min: Score, max: Score },
+        ZRank = 46 as zrank { key: Key, member: String },
+        ZCard = 47 as zcard { key: Key },
+        ZCount = 48 as zcount { key: Key, min: Score, max: Score },
+
+        ZPopMin = 49 as zpop_min { key: Key, count: u32 },
+        ZPopMax = 50 as zpop_max { key: Key, count: u32 },
+
+        ZRem = 51 as zrem { key: Key, member: String },
+        ZExpire = 52 as zexpire { key: Key, member: String, expire: u32 },
 
         //// List Commands ////
 
-        LLPush as llpush { key: String, value: ValueType },
-        LLPushNx as llpush_nx { key: String, value: ValueType },
-        LLPushEx as llpush_ex { key: String, value: ValueType, expire: u32 },
-        LRPush as lrpush { key: String, value: ValueType },
-        LRPushNx as lrpush_nx { key: String, value: ValueType },
-        LRPushEx as lrpush_ex { key: String, value: ValueType, expire: u32 },
+        LLPush = 53 as llpush { key: Key, value: ValueType },
+        LLPushNx = 54 as llpush_nx { key: Key, value: ValueType },
+        LLPushEx = 55 as llpush_ex { key: Key, value: ValueType, expire: u32 },
+        LRPush = 56 as lrpush { key: Key, value: ValueType },
+        LRPushNx = 57 as lrpush_nx { key: Key, value: ValueType },
+        LRPushEx = 58 as lrpush_ex { key: Key, value: ValueType, expire: u32 },
 
-        LExpire as lexpire { key: String, index: u32, expire: u32 },
-        LLPop as llpop { key: String, count: u32 },
-        LRPop as lrpop { key: String, count: u32 },
-        LRange as lrange { key: String, start: u32, end: u32 },
+        LExpire = 59 as lexpire { key: Key, index: u32, expire: u32 },
+        LLPop = 60 as llpop { key: Key, count: u32 },
+        LRPop = 61 as lrpop { key: Key, count: u32 },
+        LRange = 62 as lrange { key: Key, start: u32, end: u32 },
 
         //// Set Commands ////
 
-        SAdd as sadd { key: String, members: Vec<String> },
-        SAddNx as sadd_nx { key: String, members: Vec<String> },
-        SAddEx as sadd_ex { key: String, member: String, expire: u32 },
-        SMember as smember { key: String, member: String },
-        SMembers as smembers { key: String },
-
-        SExpire as sexpire { key: String, member: String, expire: u32 },
-        SRem as srem { key: String, members: Vec<String> },
-        SPop as spop { key: String, count: u32 },
+        SAdd = 63 as sadd { key: Key, members: Vec<String> },
+        SAddNx = 64 as sadd_nx { key: Key, members: Vec<String> },
+        SAddEx = 65 as sadd_ex { key: Key, member: String, expire: u32 },
+        SMember = 66 as smember { key: Key, member: String },
+        SMembers = 67 as smembers { key: Key },
+
+        SExpire = 68 as sexpire { key: Key, member: String, expire: u32 },
+        SRem = 69 as srem { key: Key, members: Vec<String> },
+        SPop = 70 as spop { key: Key, count: u32 },
+
+        SCard = 71 as scard { key: Key },
+        SRandMember = 72 as srand_member { key: Key, count: u32 },
+        SMove = 73 as smove { source: Key, destination: Key, member: String },
+        SUnion = 74 as sunion { keys: Vec<Key> },
+        SInter = 75 as sinter { keys: Vec<Key> },
+        SDiff = 76 as sdiff { keys: Vec<Key> },
+        SUnionStore = 77 as sunion_store { destination: Key, keys: Vec<Key> },
+        SInterStore = 78 as sinter_store { destination: Key, keys: Vec<Key> },
+        SDiffStore = 79 as sdiff_store { destination: Key, keys: Vec<Key> },
     } -> ServerResponse
 }
 
+impl ClientCommand {
+    /// The keys the command reads or writes.
+    pub fn keys(&self) -> Vec<&Key> {
+        use ClientCommand::*;
+        match self {
+            Ping => vec![],
+            GetDel { keys }
+            | MGet { keys }
+            | MSet { keys, .. }
+            | MSetNx { keys, .. }
+            | SUnion { keys }
+            | SInter { keys }
+            | SDiff { keys } => keys.iter().collect(),
+            SUnionStore { destination, keys }
+            | SInterStore { destination, keys }
+            | SDiffStore { destination, keys } => {
+                std::iter::once(destination).chain(keys).collect()
+            }
+            SMove {
+                source,
+                destination,
+                ..
+            } => vec![source, destination],
+            Expire { key, .. }
+            | Persist { key }
+            | Ttl { key }
+            | Del { key, .. }
+            | Decr { key }
+            | DecrBy { key, .. }
+            | Incr { key }
+            | IncrBy { key, .. }
+            | Get { key, .. }
+            | GetEx { key, .. }
+            | GetSet { key, .. }
+            | Set { key, .. }
+            | SetEx { key, .. }
+            | SetNx { key, .. }
+            | HExpire { key, .. }
+            | HDel { key, .. }
+            | HDelGet { key, .. }
+            | HPopRand { key, .. }
+            | HExists { key, .. }
+            | HGet { key, .. }
+            | HGetAll { key }
+            | HMGet { key, .. }
+            | HKeys { key }
+            | HValues { key }
+            | HLen { key }
+            | HDecr { key, .. }
+            | HDecrBy { key, .. }
+            | HIncr { key, .. }
+            | HIncrBy { key, .. }
+            | HSet { key, .. }
+            | HSetNx { key, .. }
+            | HSetEx { key, .. }
+            | HMSet { key, .. }
+            | HMSetNx { key, .. }
+            | ZAdd { key, .. }
+            | ZAddNx { key, .. }
+            | ZIncrBy { key, .. }
+            | ZDecrBy { key, .. }
+            | ZScore { key, .. }
+            | ZMScore { key, .. }
+            | ZRange { key, .. }
+            | ZRangeByScore { key, .. }
+            | ZRank { key, .. }
+            | ZCard { key }
+            | ZCount { key, .. }
+            | ZPopMin { key, .. }
+            | ZPopMax { key, .. }
+            | ZRem { key, .. }
+            | ZExpire { key, .. }
+            | LLPush { key, .. }
+            | LLPushNx { key, .. }
+            | LLPushEx { key, .. }
+            | LRPush { key, .. }
+            | LRPushNx { key, .. }
+            | LRPushEx { key, .. }
+            | LExpire { key, .. }
+            | LLPop { key, .. }
+            | LRPop { key, .. }
+            | LRange { key, .. }
+            | SAdd { key, .. }
+            | SAddNx { key, .. }
+            | SAddEx { key, .. }
+            | SMember { key, .. }
+            | SMembers { key }
+            | SExpire { key, .. }
+            | SRem { key, .. }
+            | SPop { key, .. }
+            | SCard { key }
+            | SRandMember { key, .. } => vec![key],
+        }
+    }
+
+    /// Whether applying the command twice leaves the same state as applying it once.
+    pub fn is_idempotent(&self) -> bool {
+        use ClientCommand::*;
+        // deleting a list item shifts the next one into its place
+        if let Del { path, .. } = self {
+            return !matches!(path.last(), Some(Instruction::Index(_)));
+        }
+        matches!(
+            self,
+            Persist { .. }
+                | Ttl { .. }
+                | Ping
+                | Get { .. }
+                | MGet { .. }
+                | Set { .. }
+                | MSet { .. }
+                | HExists { .. }
+                | HGet { .. }
+                | HGetAll { .. }
+                | HMGet { .. }
+                | HKeys { .. }
+                | HValues { .. }
+                | HLen { .. }
+                | HSet { .. }
+                | HMSet { .. }
+                | ZScore { .. }
+                | ZMScore { .. }
+                | ZRange { .. }
+                | ZRangeByScore { .. }
+                | ZRank { .. }
+                | ZCard { .. }
+                | ZCount { .. }
+                | ZAdd { .. }
+                | LRange { .. }
+                | SMember { .. }
+                | SMembers { .. }
+                | SCard { .. }
+                | SUnion { .. }
+                | SInter { .. }
+                | SDiff { .. }
+        )
+    }
+}
+
 pub trait PacketSender<T, R>: Sized {
     fn send(self, packet: &T) -> impl Future<Output = Result<R>>;
 }
+
+/// Collects packets to be sent together later, such as a pipeline.
+pub trait PacketQueue<T> {
+    fn queue(&mut self, packet: T);
+}
@@ -0,0 +1,113 @@
+use crate::Packet;
+use errors::{InfernoError, PacketsError};
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Neg;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Sorted set score. Any float except NaN, infinities included, ordered by `f64::total_cmp` with
+/// `-0.0` folded into `0.0` so equal scores compare equal.
+#[derive(Clone, Copy, Default)]
+pub struct Score(f64);
+
+impl Score {
+    pub const MIN: Score = Score(f64::NEG_INFINITY);
+    pub const MAX: Score = Score(f64::INFINITY);
+
+    pub fn new(value: f64) -> Option<Self> {
+        if value.is_nan() {
+            None
+        } else {
+            // adding 0.0 turns -0.0 into 0.0
+            Some(Self(value + 0.0))
+        }
+    }
+
+    pub fn get(self) -> f64 {
+        self.0
+    }
+
+    /// Adds `by`, returning `None` when the sum is NaN (an infinity plus its negation).
+    pub fn checked_add(self, by: Score) -> Option<Score> {
+        Score::new(self.0 + by.0)
+    }
+}
+
+impl PartialEq for Score {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Score {}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Score {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl Hash for Score {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
+}
+
+impl fmt::Debug for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+
+    fn neg(self) -> Self::Output {
+        Self(-self.0 + 0.0)
+    }
+}
+
+impl From<i32> for Score {
+    fn from(value: i32) -> Self {
+        Self(value as f64)
+    }
+}
+
+impl TryFrom<f64> for Score {
+    type Error = PacketsError;
+
+    fn try_from(value: f64) -> Result<Self, Self::Error> {
+        Score::new(value).ok_or(PacketsError::InvalidScore)
+    }
+}
+
+impl Packet for Score {
+    async fn write<W>(&self, stream: &mut W) -> errors::Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        stream.write_f64(self.0).await?;
+        Ok(())
+    }
+
+    async fn read<R>(stream: &mut R) -> errors::Result<Self>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let value = stream.read_f64().await?;
+        Score::new(value).ok_or(InfernoError::Packets(PacketsError::InvalidScore))
+    }
+}
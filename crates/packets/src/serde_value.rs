@@ -0,0 +1,624 @@
+//! Converts `Serialize` types to and from [`ValueType`].
+//!
+//! Structs and maps become [`ValueType::Map`], sequences and tuples [`ValueType::List`].
+//! Enum variants are written as their name, or a single entry map from the name to their
+//! fields.
+
+use crate::value::{Float, ValueType};
+use crate::Packet;
+use errors::PacketsError;
+use serde::de::value::{MapDeserializer, SeqDeserializer, StringDeserializer};
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use serde::ser::{self, Serialize};
+use std::fmt;
+
+/// Converts `value` into a [`ValueType`].
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> errors::Result<ValueType> {
+    Ok(value.serialize(ValueSerializer)?)
+}
+
+/// Reads a `T` back from a [`ValueType`].
+pub fn from_value<T: DeserializeOwned>(value: ValueType) -> errors::Result<T> {
+    Ok(T::deserialize(value)?)
+}
+
+/// Converts a struct or map into `(field, value)` pairs, as stored in a hash.
+pub fn to_fields<T: Serialize + ?Sized>(value: &T) -> errors::Result<Vec<(String, ValueType)>> {
+    match to_value(value)? {
+        ValueType::Map(fields) => Ok(fields),
+        other => Err(PacketsError::UnexpectedType {
+            expected: "map",
+            found: other.type_name(),
+        }
+        .into()),
+    }
+}
+
+/// Reads a `T` back from the `(field, value)` pairs of a hash.
+pub fn from_fields<T: DeserializeOwned>(fields: Vec<(String, ValueType)>) -> errors::Result<T> {
+    from_value(ValueType::Map(fields))
+}
+
+/// Encodes `value` into a single binary value, using the packet encoding of [`ValueType`].
+pub async fn to_blob<T: Serialize + ?Sized>(value: &T) -> errors::Result<Vec<u8>> {
+    let mut blob = Vec::new();
+    to_value(value)?.write(&mut blob).await?;
+    Ok(blob)
+}
+
+/// Reads a `T` back from a value written by [`to_blob`].
+pub async fn from_blob<T: DeserializeOwned>(mut blob: &[u8]) -> errors::Result<T> {
+    from_value(ValueType::read(&mut blob).await?)
+}
+
+/// Error raised by serde while converting, turned into `PacketsError::Serde`.
+#[derive(Debug)]
+pub struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+impl From<Error> for errors::InfernoError {
+    fn from(value: Error) -> Self {
+        PacketsError::Serde(value.0).into()
+    }
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = ValueType;
+    type Error = Error;
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeList;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeMap;
+    type SerializeStructVariant = SerializeMap;
+
+    fn serialize_bool(self, v: bool) -> Result<ValueType> {
+        Ok(ValueType::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<ValueType> {
+        Ok(ValueType::Int(v.into()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<ValueType> {
+        Ok(ValueType::Int(v.into()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<ValueType> {
+        Ok(ValueType::Int(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<ValueType> {
+        Ok(ValueType::Int64(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<ValueType> {
+        Ok(ValueType::UInt(v.into()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<ValueType> {
+        Ok(ValueType::UInt(v.into()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<ValueType> {
+        Ok(ValueType::UInt(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<ValueType> {
+        Ok(ValueType::UInt64(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<ValueType> {
+        Ok(ValueType::Float(Float(v.into())))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<ValueType> {
+        Ok(ValueType::Float(Float(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<ValueType> {
+        Ok(ValueType::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<ValueType> {
+        Ok(ValueType::String(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<ValueType> {
+        Ok(ValueType::Bytes(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<ValueType> {
+        Ok(ValueType::None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<ValueType> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<ValueType> {
+        Ok(ValueType::None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<ValueType> {
+        Ok(ValueType::None)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<ValueType> {
+        Ok(ValueType::String(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<ValueType> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<ValueType> {
+        Ok(ValueType::Map(vec![(
+            variant.to_string(),
+            value.serialize(self)?,
+        )]))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeList> {
+        Ok(SerializeList::new(None, len.unwrap_or_default()))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeList> {
+        Ok(SerializeList::new(None, len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SerializeList> {
+        Ok(SerializeList::new(None, len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeList> {
+        Ok(SerializeList::new(Some(variant), len))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<SerializeMap> {
+        Ok(SerializeMap::new(None, len.unwrap_or_default()))
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeMap> {
+        Ok(SerializeMap::new(None, len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeMap> {
+        Ok(SerializeMap::new(Some(variant), len))
+    }
+}
+
+// wraps the finished value in a single entry map when it belongs to an enum variant
+fn tagged(variant: Option<&'static str>, value: ValueType) -> ValueType {
+    match variant {
+        Some(variant) => ValueType::Map(vec![(variant.to_string(), value)]),
+        None => value,
+    }
+}
+
+struct SerializeList {
+    variant: Option<&'static str>,
+    values: Vec<ValueType>,
+}
+
+impl SerializeList {
+    fn new(variant: Option<&'static str>, len: usize) -> Self {
+        Self {
+            variant,
+            values: Vec::with_capacity(len),
+        }
+    }
+
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.values.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<ValueType> {
+        Ok(tagged(self.variant, ValueType::List(self.values)))
+    }
+}
+
+impl ser::SerializeSeq for SerializeList {
+    type Ok = ValueType;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<ValueType> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = ValueType;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<ValueType> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = ValueType;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<ValueType> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeList {
+    type Ok = ValueType;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<ValueType> {
+        self.finish()
+    }
+}
+
+struct SerializeMap {
+    variant: Option<&'static str>,
+    fields: Vec<(String, ValueType)>,
+    key: Option<String>,
+}
+
+impl SerializeMap {
+    fn new(variant: Option<&'static str>, len: usize) -> Self {
+        Self {
+            variant,
+            fields: Vec::with_capacity(len),
+            key: None,
+        }
+    }
+
+    fn finish(self) -> Result<ValueType> {
+        Ok(tagged(self.variant, ValueType::Map(self.fields)))
+    }
+}
+
+// map keys are strings, numbers and unit variants are written out as text
+fn map_key(key: ValueType) -> Result<String> {
+    match key {
+        ValueType::String(key) => Ok(key),
+        ValueType::Int(int) => Ok(int.to_string()),
+        ValueType::Int64(int) => Ok(int.to_string()),
+        ValueType::UInt(int) => Ok(int.to_string()),
+        ValueType::UInt64(int) => Ok(int.to_string()),
+        ValueType::Bool(bool) => Ok(bool.to_string()),
+        other => Err(ser::Error::custom(format_args!(
+            "map keys must be strings, found {}",
+            other.type_name()
+        ))),
+    }
+}
+
+impl ser::SerializeMap for SerializeMap {
+    type Ok = ValueType;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        self.key = Some(map_key(key.serialize(ValueSerializer)?)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| <Error as ser::Error>::custom("map value without a key"))?;
+        self.fields.push((key, value.serialize(ValueSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<ValueType> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for SerializeMap {
+    type Ok = ValueType;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.fields
+            .push((key.to_string(), value.serialize(ValueSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<ValueType> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeMap {
+    type Ok = ValueType;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.fields
+            .push((key.to_string(), value.serialize(ValueSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<ValueType> {
+        self.finish()
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for ValueType {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> de::Deserializer<'de> for ValueType {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            ValueType::None => visitor.visit_unit(),
+            ValueType::Int(int) => visitor.visit_i32(int),
+            ValueType::Int64(int) => visitor.visit_i64(int),
+            ValueType::UInt(int) => visitor.visit_u32(int),
+            ValueType::UInt64(int) => visitor.visit_u64(int),
+            ValueType::Float(Float(float)) => visitor.visit_f64(float),
+            ValueType::Bool(bool) => visitor.visit_bool(bool),
+            ValueType::String(string) => visitor.visit_string(string),
+            ValueType::Bytes(bytes) => visitor.visit_byte_buf(bytes),
+            ValueType::List(values) => {
+                let mut values = SeqDeserializer::new(values.into_iter());
+                let value = visitor.visit_seq(&mut values)?;
+                values.end()?;
+                Ok(value)
+            }
+            ValueType::Map(fields) => {
+                let fields = fields
+                    .into_iter()
+                    .map(|(field, value)| (MapKey(field), value));
+                let mut fields = MapDeserializer::new(fields);
+                let value = visitor.visit_map(&mut fields)?;
+                fields.end()?;
+                Ok(value)
+            }
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            ValueType::None => visitor.visit_none(),
+            value => visitor.visit_some(value),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self {
+            ValueType::String(variant) => {
+                let variant: StringDeserializer<Error> = variant.into_deserializer();
+                visitor.visit_enum(variant)
+            }
+            ValueType::Map(fields) if fields.len() == 1 => {
+                let (variant, value) = fields.into_iter().next().unwrap_or_default();
+                visitor.visit_enum(EnumDeserializer { variant, value })
+            }
+            other => Err(de::Error::invalid_type(unexpected(&other), &"enum variant")),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+/// A map key, parsed back into the number or bool [`map_key`] wrote as text when asked for one.
+struct MapKey(String);
+
+macro_rules! parse_map_key {
+    ($($deserialize:ident => $visit:ident),* $(,)?) => {$(
+        fn $deserialize<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+            match self.0.parse() {
+                Ok(key) => visitor.$visit(key),
+                Err(_) => Err(de::Error::invalid_value(
+                    de::Unexpected::Str(&self.0),
+                    &visitor,
+                )),
+            }
+        }
+    )*};
+}
+
+impl<'de> de::Deserializer<'de> for MapKey {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_string(self.0)
+    }
+
+    parse_map_key! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let variant: StringDeserializer<Error> = self.0.into_deserializer();
+        visitor.visit_enum(variant)
+    }
+
+    serde::forward_to_deserialize_any! {
+        i128 u128 f32 f64 char str string bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for MapKey {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+fn unexpected(value: &ValueType) -> de::Unexpected<'_> {
+    match value {
+        ValueType::None => de::Unexpected::Unit,
+        ValueType::Bool(bool) => de::Unexpected::Bool(*bool),
+        ValueType::String(string) => de::Unexpected::Str(string),
+        ValueType::Bytes(bytes) => de::Unexpected::Bytes(bytes),
+        ValueType::List(_) => de::Unexpected::Seq,
+        ValueType::Map(_) => de::Unexpected::Map,
+        other => de::Unexpected::Other(other.type_name()),
+    }
+}
+
+struct EnumDeserializer {
+    variant: String,
+    value: ValueType,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumDeserializer {
+    type Error = Error;
+    type Variant = ValueType;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, ValueType)> {
+        let variant: StringDeserializer<Error> = self.variant.into_deserializer();
+        Ok((seed.deserialize(variant)?, self.value))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for ValueType {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        de::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
@@ -1,12 +1,69 @@
+use crate::ext::PREALLOCATE_LIMIT;
+use crate::frame::{
+    check_length, read_exact_bytes, MAX_BYTES_LENGTH, MAX_COLLECTION_LENGTH, MAX_NESTING_DEPTH,
+};
 use crate::Packet;
-use errors::InfernoError;
+use errors::{InfernoError, PacketsError};
+use std::hash::{Hash, Hasher};
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum ValueType {
     None,
     Int(i32),
+    Int64(i64),
+    UInt(u32),
+    UInt64(u64),
+    Float(Float),
+    Bool(bool),
     String(String),
+    Bytes(Vec<u8>),
+    // nested documents, sent whole
+    List(Vec<ValueType>),
+    Map(Vec<(String, ValueType)>),
+}
+
+/// `f64` compared and hashed by its bits, so it can be a set member like every other value.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Float(pub f64);
+
+impl PartialEq for Float {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+
+impl Eq for Float {}
+
+impl Hash for Float {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
+}
+
+impl From<f64> for Float {
+    fn from(value: f64) -> Self {
+        Self(value)
+    }
+}
+
+impl ValueType {
+    /// Name of the variant, for error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            ValueType::None => "none",
+            ValueType::Int(_) => "int",
+            ValueType::Int64(_) => "int64",
+            ValueType::UInt(_) => "uint",
+            ValueType::UInt64(_) => "uint64",
+            ValueType::Float(_) => "float",
+            ValueType::Bool(_) => "bool",
+            ValueType::String(_) => "string",
+            ValueType::Bytes(_) => "bytes",
+            ValueType::List(_) => "list",
+            ValueType::Map(_) => "map",
+        }
+    }
 }
 
 impl Default for ValueType {
@@ -35,10 +92,62 @@ impl Packet for ValueType {
                 string.write(stream).await?;
                 Ok(())
             }
+            ValueType::Int64(int) => {
+                stream.write_u8(5).await?;
+                stream.write_i64(*int).await?;
+                Ok(())
+            }
+            ValueType::UInt(int) => {
+                stream.write_u8(6).await?;
+                stream.write_u32(*int).await?;
+                Ok(())
+            }
+            ValueType::UInt64(int) => {
+                stream.write_u8(7).await?;
+                stream.write_u64(*int).await?;
+                Ok(())
+            }
+            ValueType::Float(float) => {
+                stream.write_u8(8).await?;
+                stream.write_f64(float.0).await?;
+                Ok(())
+            }
+            ValueType::Bool(bool) => {
+                stream.write_u8(9).await?;
+                stream.write_u8(*bool as u8).await?;
+                Ok(())
+            }
+            ValueType::Bytes(bytes) => {
+                stream.write_u8(10).await?;
+                stream.write_u32(bytes.len() as u32).await?;
+                stream.write_all(bytes).await?;
+                Ok(())
+            }
+            // boxed as the nested values are written by this same function
+            ValueType::List(values) => {
+                stream.write_u8(3).await?;
+                Box::pin(values.write(stream)).await?;
+                Ok(())
+            }
+            ValueType::Map(fields) => {
+                stream.write_u8(4).await?;
+                Box::pin(fields.write(stream)).await?;
+                Ok(())
+            }
         }
     }
 
     async fn read<R>(stream: &mut R) -> errors::Result<Self>
+    where
+        R: AsyncRead + Unpin,
+    {
+        Self::read_nested(stream, 0).await
+    }
+}
+
+impl ValueType {
+    // `depth` counts the lists and maps around this value, checked before stepping into another
+    async fn read_nested<R>(stream: &mut R, depth: usize) -> errors::Result<Self>
     where
         R: AsyncRead + Unpin,
     {
@@ -47,9 +156,46 @@ impl Packet for ValueType {
             0 => Ok(ValueType::None),
             1 => Ok(ValueType::Int(stream.read_i32().await?)),
             2 => Ok(ValueType::String(String::read(stream).await?)),
-            _ => Err(InfernoError::Packets(
-                errors::PacketsError::UnknownValueType(value),
-            )),
+            3 => {
+                let length = Self::read_nested_length(stream, depth).await?;
+                let mut values = Vec::with_capacity(length.min(PREALLOCATE_LIMIT));
+                for _ in 0..length {
+                    values.push(Box::pin(Self::read_nested(stream, depth + 1)).await?);
+                }
+                Ok(ValueType::List(values))
+            }
+            4 => {
+                let length = Self::read_nested_length(stream, depth).await?;
+                let mut fields = Vec::with_capacity(length.min(PREALLOCATE_LIMIT));
+                for _ in 0..length {
+                    let field = String::read(stream).await?;
+                    fields.push((field, Box::pin(Self::read_nested(stream, depth + 1)).await?));
+                }
+                Ok(ValueType::Map(fields))
+            }
+            5 => Ok(ValueType::Int64(stream.read_i64().await?)),
+            6 => Ok(ValueType::UInt(stream.read_u32().await?)),
+            7 => Ok(ValueType::UInt64(stream.read_u64().await?)),
+            8 => Ok(ValueType::Float(Float(stream.read_f64().await?))),
+            9 => Ok(ValueType::Bool(stream.read_u8().await? != 0)),
+            10 => {
+                let length = check_length(stream.read_u32().await?, MAX_BYTES_LENGTH)?;
+                Ok(ValueType::Bytes(read_exact_bytes(stream, length).await?))
+            }
+            _ => Err(InfernoError::Packets(PacketsError::UnknownValueType(value))),
+        }
+    }
+
+    async fn read_nested_length<R>(stream: &mut R, depth: usize) -> errors::Result<usize>
+    where
+        R: AsyncRead + Unpin,
+    {
+        if depth >= MAX_NESTING_DEPTH {
+            return Err(PacketsError::NestingTooDeep {
+                limit: MAX_NESTING_DEPTH,
+            }
+            .into());
         }
+        check_length(stream.read_u32().await?, MAX_COLLECTION_LENGTH)
     }
 }
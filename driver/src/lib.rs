@@ -1,63 +1,489 @@
+pub mod pool;
 pub mod prelude;
+pub mod retry;
+pub mod typed;
 
-use errors::Result;
-use packets::{ClientCommand, Packet, PacketSender, ServerResponse};
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use errors::{InfernoError, Result};
+use packets::envelope::{Envelope, RequestId};
+use packets::frame;
+use packets::handshake::{Capabilities, Hello};
+use packets::{ClientCommand, PacketQueue, PacketSender, ServerResponse};
+use retry::{ReconnectEvent, ReconnectPolicy, RetryPolicy};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::AsyncWriteExt;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
 
+/// Connection to a server, reconnected according to its [`ReconnectPolicy`].
 pub struct Client {
-    stream: tokio::net::TcpStream,
+    connection: Connection,
+    next_id: RequestId,
+    addr: String,
+    max_frame_size: u32,
+    reconnect: ReconnectPolicy,
+}
+
+struct Connection {
+    write: OwnedWriteHalf,
+    // set while frames are being written, a write given up part way leaves a partial frame behind
+    writing: bool,
+    pending: Pending,
+    reader: ReaderTask,
+    capabilities: Capabilities,
+}
+
+impl Connection {
+    async fn open(addr: &str, max_frame_size: u32) -> Result<Self> {
+        let mut stream = tokio::net::TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+
+        let local = Hello::default();
+        frame::write_packet(&mut stream, &local).await?;
+        let remote = frame::read_packet::<Hello, _>(&mut stream, max_frame_size).await?;
+        let hello = local.negotiate(&remote)?;
+
+        let (read, write) = stream.into_split();
+        let pending = Pending::new();
+        let reader = ReaderTask(tokio::spawn(read_responses(
+            read,
+            pending.clone(),
+            max_frame_size,
+        )));
+        Ok(Self {
+            write,
+            writing: false,
+            pending,
+            reader,
+            capabilities: hello.capabilities,
+        })
+    }
 }
 
 impl Client {
     pub async fn connect(addr: &str) -> Result<Self> {
-        let stream = tokio::net::TcpStream::connect(addr).await?;
-        Ok(Self { stream })
+        Self::connect_with_max_frame_size(addr, frame::DEFAULT_MAX_FRAME_SIZE).await
+    }
+
+    /// Connects, rejecting any response frame larger than `max_frame_size` bytes.
+    pub async fn connect_with_max_frame_size(addr: &str, max_frame_size: u32) -> Result<Self> {
+        Ok(Self {
+            connection: Connection::open(addr, max_frame_size).await?,
+            next_id: 0,
+            addr: addr.to_string(),
+            max_frame_size,
+            reconnect: ReconnectPolicy::default(),
+        })
+    }
+
+    /// Replaces the default [`ReconnectPolicy`].
+    pub fn with_reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect = policy;
+        self
+    }
+
+    /// Sends the next commands with `policy` instead of the client's default.
+    pub fn retry(&mut self, policy: RetryPolicy) -> Retrying<'_> {
+        Retrying {
+            client: self,
+            policy,
+        }
+    }
+
+    /// Capabilities agreed on with the server during the handshake.
+    pub fn capabilities(&self) -> Capabilities {
+        self.connection.capabilities
+    }
+
+    /// Whether the connection has been lost, the next command reconnects first.
+    pub fn is_closed(&self) -> bool {
+        self.connection.writing || self.connection.pending.is_closed()
     }
 
     pub fn into_ref(self) -> ClientRef {
         ClientRef::from(self)
     }
+
+    /// Starts a pipeline, queueing commands to be sent in a single write.
+    pub fn pipeline(&mut self) -> Pipeline<'_> {
+        Pipeline {
+            client: self,
+            commands: Vec::new(),
+        }
+    }
+
+    async fn send_with(
+        &mut self,
+        packet: &ClientCommand,
+        policy: RetryPolicy,
+    ) -> Result<ServerResponse> {
+        let mut retries = 0;
+        loop {
+            self.ensure_connected().await?;
+            let result = self
+                .send_all(std::slice::from_ref(packet))
+                .await
+                .pop()
+                .unwrap_or(Err(InfernoError::ConnectionClosed));
+            match result {
+                Err(err)
+                    if retry::is_connection_error(&err)
+                        && policy.allows(packet)
+                        && retries < self.reconnect.max_attempts =>
+                {
+                    log::debug!("Retrying {:?} after {}", packet, err);
+                    retries += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    // nothing has been sent on a closed connection, so reconnecting is always safe
+    async fn ensure_connected(&mut self) -> Result<()> {
+        if !self.is_closed() {
+            return Ok(());
+        }
+        let policy = &self.reconnect;
+        if policy.max_attempts == 0 {
+            return Err(InfernoError::ConnectionClosed);
+        }
+
+        policy.notify(ReconnectEvent::Disconnected);
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let delay = policy.backoff.delay(attempt);
+            policy.notify(ReconnectEvent::Reconnecting { attempt, delay });
+            tokio::time::sleep(delay).await;
+
+            match Connection::open(&self.addr, self.max_frame_size).await {
+                Ok(connection) => {
+                    self.connection = connection;
+                    policy.notify(ReconnectEvent::Reconnected { attempts: attempt });
+                    return Ok(());
+                }
+                Err(err) if attempt < policy.max_attempts => {
+                    log::debug!("Reconnect attempt {} failed: {}", attempt, err);
+                }
+                Err(err) => {
+                    policy.notify(ReconnectEvent::Failed {
+                        attempts: attempt,
+                        error: &err,
+                    });
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    // frames every command into one buffer so they go out in a single write
+    async fn send_all(&mut self, packets: &[ClientCommand]) -> Vec<Result<ServerResponse>> {
+        let mut payload = Vec::new();
+        let mut ids = Vec::with_capacity(packets.len());
+        for packet in packets {
+            let id = self.next_id;
+            self.next_id = self.next_id.wrapping_add(1);
+            if let Err(err) = encode_frame(id, packet, &mut payload).await {
+                return fail_all(err, packets.len());
+            }
+            ids.push(id);
+        }
+
+        let mut responses = Vec::with_capacity(ids.len());
+        for id in &ids {
+            match self.connection.pending.register(*id) {
+                Ok(response) => responses.push(response),
+                Err(err) => {
+                    ids.iter()
+                        .for_each(|id| self.connection.pending.forget(*id));
+                    return fail_all(err, packets.len());
+                }
+            }
+        }
+
+        // stays set if this future is dropped mid-write, so the next command reconnects
+        self.connection.writing = true;
+        if let Err(err) = self.connection.write.write_all(&payload).await {
+            // a partial frame may have gone out, nothing more can be sent after it
+            self.connection.pending.close();
+            return fail_all(err.into(), packets.len());
+        }
+        self.connection.writing = false;
+
+        let mut results = Vec::with_capacity(responses.len());
+        for response in responses {
+            results.push(match response.await {
+                Ok(response) => into_result(response),
+                Err(_) => Err(InfernoError::ConnectionClosed),
+            });
+        }
+        results
+    }
 }
 
 impl PacketSender<ClientCommand, ServerResponse> for &mut Client {
     async fn send(self, packet: &ClientCommand) -> Result<ServerResponse> {
-        packet.write(&mut self.stream).await?;
+        let policy = self.reconnect.retry;
+        self.send_with(packet, policy).await
+    }
+}
+
+/// A [`Client`] sending commands with its own [`RetryPolicy`], see [`Client::retry`].
+pub struct Retrying<'a> {
+    client: &'a mut Client,
+    policy: RetryPolicy,
+}
+
+impl PacketSender<ClientCommand, ServerResponse> for Retrying<'_> {
+    async fn send(self, packet: &ClientCommand) -> Result<ServerResponse> {
+        self.client.send_with(packet, self.policy).await
+    }
+}
+
+/// Commands queued on a [`Client`], sent together by [`Pipeline::execute`].
+///
+/// Queue commands with the `ClientCommandQueue` methods, named like their
+/// `ClientCommandExecutor` counterparts. The server runs queued commands on the same key
+/// in queue order, so each sees the writes queued before it on that key; commands on other
+/// keys may run concurrently. A closed connection is reconnected before sending, but
+/// commands are never resent.
+pub struct Pipeline<'a> {
+    client: &'a mut Client,
+    commands: Vec<ClientCommand>,
+}
 
-        let response = ServerResponse::read(&mut self.stream).await?;
+impl Pipeline<'_> {
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
 
-        if let ServerResponse::Error { err } = response {
-            Err(err)
-        } else {
-            Ok(response)
+    /// Flushes every queued command and returns their results in queue order.
+    pub async fn execute(self) -> Vec<Result<ServerResponse>> {
+        if self.commands.is_empty() {
+            return Vec::new();
+        }
+        if let Err(err) = self.client.ensure_connected().await {
+            return fail_all(err, self.commands.len());
+        }
+        self.client.send_all(&self.commands).await
+    }
+}
+
+impl PacketQueue<ClientCommand> for Pipeline<'_> {
+    fn queue(&mut self, packet: ClientCommand) {
+        self.commands.push(packet);
+    }
+}
+
+async fn encode_frame(id: RequestId, packet: &ClientCommand, buffer: &mut Vec<u8>) -> Result<()> {
+    let mut payload = Vec::new();
+    Envelope::write_tagged(id, packet, &mut payload).await?;
+    frame::write_frame(buffer, &payload).await
+}
+
+// only the first entry carries the cause, errors can't be cloned
+fn fail_all(err: InfernoError, count: usize) -> Vec<Result<ServerResponse>> {
+    std::iter::once(Err(err))
+        .chain((1..count).map(|_| Err(InfernoError::ConnectionClosed)))
+        .collect()
+}
+
+fn into_result(response: ServerResponse) -> Result<ServerResponse> {
+    if let ServerResponse::Error { err } = response {
+        Err(err)
+    } else {
+        Ok(response)
+    }
+}
+
+type Waiters = HashMap<RequestId, oneshot::Sender<ServerResponse>>;
+
+/// Requests waiting for their response, `None` once the connection is gone.
+#[derive(Clone)]
+struct Pending(Arc<Mutex<Option<Waiters>>>);
+
+impl Pending {
+    fn new() -> Self {
+        Self(Arc::new(Mutex::new(Some(HashMap::new()))))
+    }
+
+    fn register(&self, id: RequestId) -> Result<oneshot::Receiver<ServerResponse>> {
+        let (sender, receiver) = oneshot::channel();
+        let mut pending = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        let pending = pending.as_mut().ok_or(InfernoError::ConnectionClosed)?;
+        pending.insert(id, sender);
+        Ok(receiver)
+    }
+
+    fn forget(&self, id: RequestId) {
+        let mut pending = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(pending) = pending.as_mut() {
+            pending.remove(&id);
+        }
+    }
+
+    fn resolve(&self, response: Envelope<ServerResponse>) {
+        let mut pending = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        match pending
+            .as_mut()
+            .and_then(|pending| pending.remove(&response.id))
+        {
+            // the caller may have stopped waiting
+            Some(sender) => {
+                let _ = sender.send(response.packet);
+            }
+            None => log::warn!("Response for unknown request {}", response.id),
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.0
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .is_none()
+    }
+
+    fn close(&self) {
+        // dropping the senders fails every request still waiting
+        self.0.lock().unwrap_or_else(|err| err.into_inner()).take();
+    }
+}
+
+/// Reads responses until the connection closes, aborted once its owner is dropped.
+struct ReaderTask(JoinHandle<()>);
+
+impl Drop for ReaderTask {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+async fn read_responses(mut read: OwnedReadHalf, pending: Pending, max_frame_size: u32) {
+    loop {
+        match frame::read_packet::<Envelope<ServerResponse>, _>(&mut read, max_frame_size).await {
+            Ok(response) => pending.resolve(response),
+            Err(err) => {
+                log::debug!("Connection closed: {}", err);
+                break;
+            }
         }
     }
+    pending.close();
 }
 
+/// Writes at most this many bytes of queued frames at once.
+const MAX_WRITE_BATCH: usize = 64 * 1024;
+
+/// A [`Client`] shared between tasks.
+///
+/// Unlike [`Client`], a lost connection is not reconnected.
+///
+/// A background task owns the socket and writes queued commands, so clones can keep
+/// many requests in flight at once while the reader routes responses back by id.
 #[derive(Clone)]
 pub struct ClientRef {
-    shared_client: Arc<RwLock<Client>>,
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    frames: UnboundedSender<Vec<u8>>,
+    next_id: AtomicU32,
+    pending: Pending,
+    capabilities: Capabilities,
+    _reader: ReaderTask,
 }
 
 impl From<Client> for ClientRef {
     fn from(value: Client) -> Self {
+        let Client {
+            connection:
+                Connection {
+                    write,
+                    writing,
+                    pending,
+                    reader,
+                    capabilities,
+                },
+            next_id,
+            ..
+        } = value;
+        if writing {
+            pending.close();
+        }
+
+        let (frames, queued) = mpsc::unbounded_channel();
+        // exits once every handle is dropped and the queue is drained
+        tokio::spawn(write_frames(write, queued, pending.clone()));
         Self {
-            shared_client: Arc::new(RwLock::new(value)),
+            shared: Arc::new(Shared {
+                frames,
+                next_id: AtomicU32::new(next_id),
+                pending,
+                capabilities,
+                _reader: reader,
+            }),
         }
     }
 }
 
 impl ClientRef {
     pub async fn connect(addr: &str) -> Result<Self> {
-        let client = Client::connect(addr).await?;
-        Ok(Self {
-            shared_client: Arc::new(RwLock::new(client)),
-        })
+        Ok(Client::connect(addr).await?.into_ref())
+    }
+
+    /// Capabilities agreed on with the server during the handshake.
+    pub fn capabilities(&self) -> Capabilities {
+        self.shared.capabilities
     }
 }
 
 impl PacketSender<ClientCommand, ServerResponse> for &ClientRef {
     async fn send(self, packet: &ClientCommand) -> Result<ServerResponse> {
-        self.shared_client.write().await.send(packet).await
+        let shared = &self.shared;
+        // wraps around on overflow like `Client`
+        let id = shared.next_id.fetch_add(1, Ordering::Relaxed);
+
+        let mut frame = Vec::new();
+        encode_frame(id, packet, &mut frame).await?;
+
+        let response = shared.pending.register(id)?;
+        if shared.frames.send(frame).is_err() {
+            shared.pending.forget(id);
+            return Err(InfernoError::ConnectionClosed);
+        }
+
+        match response.await {
+            Ok(response) => into_result(response),
+            Err(_) => Err(InfernoError::ConnectionClosed),
+        }
+    }
+}
+
+// coalesces frames queued by concurrent callers into as few writes as possible
+async fn write_frames(
+    mut write: OwnedWriteHalf,
+    mut frames: UnboundedReceiver<Vec<u8>>,
+    pending: Pending,
+) {
+    while let Some(mut batch) = frames.recv().await {
+        while batch.len() < MAX_WRITE_BATCH {
+            match frames.try_recv() {
+                Ok(frame) => batch.extend_from_slice(&frame),
+                Err(_) => break,
+            }
+        }
+        if let Err(err) = write.write_all(&batch).await {
+            log::debug!("Connection closed: {}", err);
+            break;
+        }
     }
+    pending.close();
 }
@@ -0,0 +1,242 @@
+use crate::Client;
+use errors::Result;
+use packets::{ClientCommand, ClientCommandExecutor, PacketSender, ServerResponse};
+use std::collections::VecDeque;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
+
+/// Sizing and timeouts of a [`Pool`].
+#[derive(Clone, Debug)]
+pub struct PoolOptions {
+    /// Connections opened up front and kept open even when idle, lost ones are reopened in the
+    /// background.
+    pub min_size: usize,
+    /// Connections open at once, further checkouts wait their turn.
+    pub max_size: usize,
+    /// Idle connections above `min_size` are closed after this long.
+    pub idle_timeout: Duration,
+    /// How long an idle connection has to answer a ping at checkout before it is replaced.
+    pub check_timeout: Duration,
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        Self {
+            min_size: 1,
+            max_size: 16,
+            idle_timeout: Duration::from_secs(300),
+            check_timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Pool of [`Client`] connections to one server.
+///
+/// Waiting checkouts are served first come, first served. Idle connections are pinged at
+/// checkout, one that is closed or doesn't answer in time is discarded and replaced.
+#[derive(Clone)]
+pub struct Pool {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    addr: String,
+    options: PoolOptions,
+    idle: Mutex<VecDeque<Idle>>,
+    // one permit per connection that may be open, tokio hands them out in order
+    permits: Arc<Semaphore>,
+}
+
+struct Idle {
+    client: Client,
+    since: Instant,
+}
+
+impl Pool {
+    pub async fn connect(addr: &str) -> Result<Self> {
+        Self::connect_with(addr, PoolOptions::default()).await
+    }
+
+    /// Opens `min_size` connections, `max_size` is raised to at least `min_size` and one.
+    pub async fn connect_with(addr: &str, mut options: PoolOptions) -> Result<Self> {
+        options.max_size = options.max_size.max(options.min_size).max(1);
+
+        let now = Instant::now();
+        let mut idle = VecDeque::with_capacity(options.max_size);
+        for _ in 0..options.min_size {
+            idle.push_back(Idle {
+                client: Client::connect(addr).await?,
+                since: now,
+            });
+        }
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                addr: addr.to_string(),
+                permits: Arc::new(Semaphore::new(options.max_size)),
+                idle: Mutex::new(idle),
+                options,
+            }),
+        })
+    }
+
+    /// Checks out a connection, waiting for one to be returned if `max_size` are in use.
+    pub async fn get(&self) -> Result<PooledClient> {
+        let permit = self
+            .inner
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("pool semaphore is never closed");
+
+        let client = loop {
+            match self.inner.pop_idle() {
+                Some(mut client) => {
+                    if self.inner.check(&mut client).await {
+                        break client;
+                    }
+                }
+                None => break Client::connect(&self.inner.addr).await?,
+            }
+        };
+        // idle connections found broken on the way are replaced
+        self.inner.refill();
+        Ok(PooledClient::new(client, permit, self.inner.clone()))
+    }
+
+    /// Connections currently open, idle or checked out.
+    pub fn size(&self) -> usize {
+        self.idle() + self.inner.in_use()
+    }
+
+    /// Connections waiting in the pool.
+    pub fn idle(&self) -> usize {
+        self.inner.lock_idle().len()
+    }
+}
+
+impl Inner {
+    fn lock_idle(&self) -> std::sync::MutexGuard<'_, VecDeque<Idle>> {
+        self.idle.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn in_use(&self) -> usize {
+        self.options.max_size - self.permits.available_permits()
+    }
+
+    // a half-open connection looks open until a round trip over it never completes
+    async fn check(&self, client: &mut Client) -> bool {
+        if client.is_closed() {
+            return false;
+        }
+        let ping = tokio::time::timeout(self.options.check_timeout, (&mut *client).ping());
+        matches!(ping.await, Ok(Ok(ServerResponse::Ok)))
+    }
+
+    // most recently returned first, so rarely used connections are left to time out
+    fn pop_idle(&self) -> Option<Client> {
+        let mut idle = self.lock_idle();
+        self.prune(&mut idle);
+        idle.pop_back().map(|idle| idle.client)
+    }
+
+    fn push_idle(&self, client: Client) {
+        let mut idle = self.lock_idle();
+        idle.push_back(Idle {
+            client,
+            since: Instant::now(),
+        });
+        self.prune(&mut idle);
+    }
+
+    // callers hold a permit which goes with one of the idle connections, so it isn't counted twice
+    fn prune(&self, idle: &mut VecDeque<Idle>) {
+        let now = Instant::now();
+        let in_use = self.in_use().saturating_sub(1);
+        while let Some(oldest) = idle.front() {
+            let expired = now.duration_since(oldest.since) >= self.options.idle_timeout;
+            let surplus = idle.len() + in_use > self.options.min_size;
+            if !(oldest.client.is_closed() || expired && surplus) {
+                break;
+            }
+            idle.pop_front();
+        }
+    }
+
+    // reopens connections in the background until `min_size` are open, each holds a permit while
+    // connecting so it counts as open and refills running at once don't overshoot
+    fn refill(self: &Arc<Self>) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let idle = self.lock_idle();
+        for _ in idle.len() + self.in_use()..self.options.min_size {
+            let Ok(permit) = self.permits.clone().try_acquire_owned() else {
+                break;
+            };
+            let inner = self.clone();
+            runtime.spawn(async move {
+                match Client::connect(&inner.addr).await {
+                    Ok(client) => inner.push_idle(client),
+                    Err(err) => log::debug!("Reopening a pool connection failed: {}", err),
+                }
+                drop(permit);
+            });
+        }
+    }
+}
+
+/// A connection checked out of a [`Pool`], returned to it on drop.
+pub struct PooledClient {
+    client: Option<Client>,
+    pool: Arc<Inner>,
+    // released after the client is back in the pool
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl PooledClient {
+    fn new(client: Client, permit: OwnedSemaphorePermit, pool: Arc<Inner>) -> Self {
+        Self {
+            client: Some(client),
+            pool,
+            permit: Some(permit),
+        }
+    }
+}
+
+impl Deref for PooledClient {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        self.client.as_ref().expect("client is only taken on drop")
+    }
+}
+
+impl DerefMut for PooledClient {
+    fn deref_mut(&mut self) -> &mut Client {
+        self.client.as_mut().expect("client is only taken on drop")
+    }
+}
+
+impl Drop for PooledClient {
+    fn drop(&mut self) {
+        if let Some(client) = self.client.take() {
+            if !client.is_closed() {
+                self.pool.push_idle(client);
+            }
+        }
+        drop(self.permit.take());
+        self.pool.refill();
+    }
+}
+
+impl PacketSender<ClientCommand, ServerResponse> for &Pool {
+    async fn send(self, packet: &ClientCommand) -> Result<ServerResponse> {
+        let mut client = self.get().await?;
+        (&mut *client).send(packet).await
+    }
+}
@@ -1,4 +1,8 @@
-pub use crate::{Client, ClientRef};
+pub use crate::pool::{Pool, PoolOptions, PooledClient};
+pub use crate::retry::{Backoff, ReconnectEvent, ReconnectPolicy, RetryPolicy};
+pub use crate::typed::{FromValue, IntoTyped, ToValue, Typed};
+pub use crate::{Client, ClientRef, Pipeline, Retrying};
 pub use packets::{
-    value::ValueType, ClientCommand, ClientCommandExecutor, Packet, PacketSender, ServerResponse,
+    handshake::Capabilities, instruction::Instruction, key::Key, value::ValueType, ClientCommand,
+    ClientCommandExecutor, ClientCommandQueue, Packet, PacketQueue, PacketSender, ServerResponse,
 };
@@ -0,0 +1,124 @@
+use errors::InfernoError;
+use packets::ClientCommand;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Which commands are sent again when the connection drops before they are answered.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum RetryPolicy {
+    /// Return the connection error to the caller.
+    Never,
+    /// Resend commands that leave the same state when applied twice.
+    #[default]
+    Idempotent,
+    /// Resend every command, which may apply it twice.
+    Always,
+}
+
+impl RetryPolicy {
+    pub fn allows(self, command: &ClientCommand) -> bool {
+        match self {
+            RetryPolicy::Never => false,
+            RetryPolicy::Idempotent => command.is_idempotent(),
+            RetryPolicy::Always => true,
+        }
+    }
+}
+
+/// Delay before each reconnect attempt, doubling from `initial` up to `max`.
+#[derive(Clone, Copy, Debug)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(50),
+            max: Duration::from_secs(5),
+        }
+    }
+}
+
+impl Backoff {
+    /// Delay before `attempt`, counting from one.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let doublings = attempt.saturating_sub(1).min(31);
+        self.initial.saturating_mul(1 << doublings).min(self.max)
+    }
+}
+
+/// Progress of a [`Client`](crate::Client) getting its connection back.
+#[derive(Debug)]
+pub enum ReconnectEvent<'a> {
+    /// The connection was found closed, reconnecting starts.
+    Disconnected,
+    /// Attempt `attempt` is made after waiting `delay`.
+    Reconnecting {
+        attempt: u32,
+        delay: Duration,
+    },
+    Reconnected {
+        attempts: u32,
+    },
+    /// Every attempt failed, `error` is returned to the caller.
+    Failed {
+        attempts: u32,
+        error: &'a InfernoError,
+    },
+}
+
+type Hook = Arc<dyn Fn(&ReconnectEvent<'_>) + Send + Sync>;
+
+/// How a [`Client`](crate::Client) reconnects and which commands it resends.
+#[derive(Clone)]
+pub struct ReconnectPolicy {
+    pub backoff: Backoff,
+    /// Attempts before giving up, zero never reconnects.
+    pub max_attempts: u32,
+    /// Used for commands not sent through [`Client::retry`](crate::Client::retry).
+    pub retry: RetryPolicy,
+    hooks: Vec<Hook>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            backoff: Backoff::default(),
+            max_attempts: 5,
+            retry: RetryPolicy::default(),
+            hooks: Vec::new(),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Never reconnects, every command fails once the connection is lost.
+    pub fn disabled() -> Self {
+        Self {
+            max_attempts: 0,
+            retry: RetryPolicy::Never,
+            ..Self::default()
+        }
+    }
+
+    /// Calls `hook` for every [`ReconnectEvent`].
+    pub fn on_event<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&ReconnectEvent<'_>) + Send + Sync + 'static,
+    {
+        self.hooks.push(Arc::new(hook));
+        self
+    }
+
+    pub(crate) fn notify(&self, event: ReconnectEvent<'_>) {
+        log::debug!("{:?}", event);
+        self.hooks.iter().for_each(|hook| hook(&event));
+    }
+}
+
+// errors that mean the connection is gone, rather than the command failing
+pub(crate) fn is_connection_error(err: &InfernoError) -> bool {
+    matches!(err, InfernoError::Io(_) | InfernoError::ConnectionClosed)
+}
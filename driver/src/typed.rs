@@ -0,0 +1,632 @@
+use errors::{PacketsError, Result};
+use packets::key::Key;
+use packets::score::Score;
+#[cfg(feature = "serde")]
+use packets::serde_value;
+use packets::value::{Float, ValueType};
+use packets::{ClientCommand, ClientCommandExecutor, PacketSender, ServerResponse};
+#[cfg(feature = "serde")]
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::hash::Hash;
+use std::time::Duration;
+
+/// Converts a value sent by the server into a Rust type.
+pub trait FromValue: Sized {
+    fn from_value(value: ValueType) -> Result<Self>;
+}
+
+/// Converts a Rust type into a value to send to the server.
+pub trait ToValue {
+    fn to_value(&self) -> ValueType;
+}
+
+fn mismatch<T>(expected: &'static str, value: &ValueType) -> Result<T> {
+    Err(PacketsError::UnexpectedType {
+        expected,
+        found: value.type_name(),
+    }
+    .into())
+}
+
+/// Converts any response into `T`, bulk responses are read as a list.
+pub fn from_response<T: FromValue>(response: ServerResponse) -> Result<T> {
+    let value = match response {
+        ServerResponse::Error { err } => return Err(err),
+        ServerResponse::Ok => ValueType::None,
+        ServerResponse::Single { value } => value,
+        ServerResponse::Bulk { values } => ValueType::List(values),
+        ServerResponse::OptInt { value } => value.map_or(ValueType::None, ValueType::UInt),
+        ServerResponse::IntList { values } => {
+            ValueType::List(values.into_iter().map(ValueType::UInt).collect())
+        }
+        ServerResponse::OptScore { value } => score_value(value),
+        ServerResponse::Scores { values } => {
+            ValueType::List(values.into_iter().map(score_value).collect())
+        }
+        ServerResponse::ScoredBulk { values } => ValueType::Map(
+            values
+                .into_iter()
+                .map(|(member, score)| (member, score_value(Some(score))))
+                .collect(),
+        ),
+    };
+    T::from_value(value)
+}
+
+fn score_value(score: Option<Score>) -> ValueType {
+    score.map_or(ValueType::None, |score| {
+        ValueType::Float(Float(score.get()))
+    })
+}
+
+impl FromValue for ValueType {
+    fn from_value(value: ValueType) -> Result<Self> {
+        Ok(value)
+    }
+}
+
+macro_rules! int_from_value {
+    ($($int:ty),*) => {
+        $(
+            impl FromValue for $int {
+                fn from_value(value: ValueType) -> Result<Self> {
+                    let converted = match &value {
+                        ValueType::Int(int) => <$int>::try_from(*int).ok(),
+                        ValueType::Int64(int) => <$int>::try_from(*int).ok(),
+                        ValueType::UInt(int) => <$int>::try_from(*int).ok(),
+                        ValueType::UInt64(int) => <$int>::try_from(*int).ok(),
+                        _ => None,
+                    };
+                    match converted {
+                        Some(int) => Ok(int),
+                        None => mismatch(stringify!($int), &value),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+int_from_value!(i32, i64, u32, u64, usize);
+
+impl FromValue for f64 {
+    fn from_value(value: ValueType) -> Result<Self> {
+        match value {
+            ValueType::Float(Float(float)) => Ok(float),
+            ValueType::Int(int) => Ok(int as f64),
+            ValueType::Int64(int) => Ok(int as f64),
+            ValueType::UInt(int) => Ok(int as f64),
+            ValueType::UInt64(int) => Ok(int as f64),
+            other => mismatch("f64", &other),
+        }
+    }
+}
+
+// the server answers yes/no questions with 0 or 1
+impl FromValue for bool {
+    fn from_value(value: ValueType) -> Result<Self> {
+        match value {
+            ValueType::Bool(bool) => Ok(bool),
+            ValueType::Int(int) => Ok(int != 0),
+            ValueType::UInt(int) => Ok(int != 0),
+            other => mismatch("bool", &other),
+        }
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: ValueType) -> Result<Self> {
+        match value {
+            ValueType::String(string) => Ok(string),
+            ValueType::Bytes(bytes) => Ok(String::from_utf8(bytes)?),
+            other => mismatch("string", &other),
+        }
+    }
+}
+
+impl FromValue for Vec<u8> {
+    fn from_value(value: ValueType) -> Result<Self> {
+        match value {
+            ValueType::Bytes(bytes) => Ok(bytes),
+            ValueType::String(string) => Ok(string.into_bytes()),
+            other => mismatch("bytes", &other),
+        }
+    }
+}
+
+/// `None` for a missing value.
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: ValueType) -> Result<Self> {
+        match value {
+            ValueType::None => Ok(None),
+            value => T::from_value(value).map(Some),
+        }
+    }
+}
+
+/// A list, or a map as `(field, value)` pairs.
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: ValueType) -> Result<Self> {
+        match value {
+            ValueType::List(values) => values.into_iter().map(T::from_value).collect(),
+            ValueType::Map(fields) => fields
+                .into_iter()
+                .map(|(field, value)| {
+                    T::from_value(ValueType::List(vec![ValueType::String(field), value]))
+                })
+                .collect(),
+            other => mismatch("list", &other),
+        }
+    }
+}
+
+impl<A: FromValue, B: FromValue> FromValue for (A, B) {
+    fn from_value(value: ValueType) -> Result<Self> {
+        match value {
+            ValueType::List(values) if values.len() == 2 => {
+                let mut values = values.into_iter();
+                let first = A::from_value(values.next().unwrap_or_default())?;
+                let second = B::from_value(values.next().unwrap_or_default())?;
+                Ok((first, second))
+            }
+            other => mismatch("pair", &other),
+        }
+    }
+}
+
+impl<T: FromValue + Eq + Hash> FromValue for HashSet<T> {
+    fn from_value(value: ValueType) -> Result<Self> {
+        Ok(Vec::from_value(value)?.into_iter().collect())
+    }
+}
+
+impl<T: FromValue + Ord> FromValue for BTreeSet<T> {
+    fn from_value(value: ValueType) -> Result<Self> {
+        Ok(Vec::from_value(value)?.into_iter().collect())
+    }
+}
+
+/// A map, or a list of `(key, value)` pairs.
+impl<K: FromValue + Eq + Hash, V: FromValue> FromValue for HashMap<K, V> {
+    fn from_value(value: ValueType) -> Result<Self> {
+        Ok(Vec::<(K, V)>::from_value(value)?.into_iter().collect())
+    }
+}
+
+impl<K: FromValue + Ord, V: FromValue> FromValue for BTreeMap<K, V> {
+    fn from_value(value: ValueType) -> Result<Self> {
+        Ok(Vec::<(K, V)>::from_value(value)?.into_iter().collect())
+    }
+}
+
+impl<T: ToValue + ?Sized> ToValue for &T {
+    fn to_value(&self) -> ValueType {
+        (**self).to_value()
+    }
+}
+
+impl ToValue for ValueType {
+    fn to_value(&self) -> ValueType {
+        self.clone()
+    }
+}
+
+macro_rules! to_value {
+    ($($ty:ty => $variant:ident),*$(,)?) => {
+        $(
+            impl ToValue for $ty {
+                fn to_value(&self) -> ValueType {
+                    ValueType::$variant((*self).into())
+                }
+            }
+        )*
+    };
+}
+
+to_value! {
+    i32 => Int,
+    i64 => Int64,
+    u32 => UInt,
+    u64 => UInt64,
+    f64 => Float,
+    bool => Bool,
+}
+
+impl ToValue for str {
+    fn to_value(&self) -> ValueType {
+        ValueType::String(self.to_string())
+    }
+}
+
+impl ToValue for String {
+    fn to_value(&self) -> ValueType {
+        ValueType::String(self.clone())
+    }
+}
+
+impl ToValue for [u8] {
+    fn to_value(&self) -> ValueType {
+        ValueType::Bytes(self.to_vec())
+    }
+}
+
+impl ToValue for Vec<u8> {
+    fn to_value(&self) -> ValueType {
+        ValueType::Bytes(self.clone())
+    }
+}
+
+impl<T: ToValue> ToValue for Option<T> {
+    fn to_value(&self) -> ValueType {
+        self.as_ref().map_or(ValueType::None, T::to_value)
+    }
+}
+
+impl<T: ToValue> ToValue for Vec<T> {
+    fn to_value(&self) -> ValueType {
+        ValueType::List(self.iter().map(T::to_value).collect())
+    }
+}
+
+impl<T: ToValue> ToValue for HashMap<String, T> {
+    fn to_value(&self) -> ValueType {
+        ValueType::Map(
+            self.iter()
+                .map(|(field, value)| (field.clone(), value.to_value()))
+                .collect(),
+        )
+    }
+}
+
+impl<T: ToValue> ToValue for BTreeMap<String, T> {
+    fn to_value(&self) -> ValueType {
+        ValueType::Map(
+            self.iter()
+                .map(|(field, value)| (field.clone(), value.to_value()))
+                .collect(),
+        )
+    }
+}
+
+/// Wraps any sender in [`Typed`].
+pub trait IntoTyped: PacketSender<ClientCommand, ServerResponse> {
+    fn typed(self) -> Typed<Self> {
+        Typed(self)
+    }
+}
+
+impl<S: PacketSender<ClientCommand, ServerResponse>> IntoTyped for S {}
+
+/// Commands taking and returning Rust types, converted with [`ToValue`] and [`FromValue`].
+///
+/// A response of the wrong shape fails with `PacketsError::UnexpectedType`.
+pub struct Typed<S>(S);
+
+fn keys<K: Into<Key>>(keys: impl IntoIterator<Item = K>) -> Vec<Key> {
+    keys.into_iter().map(Into::into).collect()
+}
+
+fn strings<S: Into<String>>(strings: impl IntoIterator<Item = S>) -> Vec<String> {
+    strings.into_iter().map(Into::into).collect()
+}
+
+// whole seconds rounded up, so a sub-second expiry doesn't drop the key right away
+fn seconds(expire: Duration) -> u32 {
+    let seconds = expire.as_secs() + u64::from(expire.subsec_nanos() > 0);
+    seconds.try_into().unwrap_or(u32::MAX)
+}
+
+fn score(score: f64) -> Result<Score> {
+    Score::new(score).ok_or_else(|| PacketsError::InvalidScore.into())
+}
+
+impl<S: PacketSender<ClientCommand, ServerResponse>> Typed<S> {
+    //// Arbitrary Commands ////
+
+    pub async fn expire(self, key: impl Into<Key>, expire: Duration) -> Result<bool> {
+        from_response(self.0.expire(key.into(), seconds(expire)).await?)
+    }
+
+    pub async fn persist(self, key: impl Into<Key>) -> Result<bool> {
+        from_response(self.0.persist(key.into()).await?)
+    }
+
+    /// `None` when the key is missing or never expires.
+    pub async fn ttl(self, key: impl Into<Key>) -> Result<Option<Duration>> {
+        let seconds: Option<u64> = from_response(self.0.ttl(key.into()).await?)?;
+        Ok(seconds.map(Duration::from_secs))
+    }
+
+    pub async fn del(self, key: impl Into<Key>) -> Result<bool> {
+        from_response(self.0.del(key.into(), vec![]).await?)
+    }
+
+    //// Value Commands ////
+
+    pub async fn get<T: FromValue>(self, key: impl Into<Key>) -> Result<T> {
+        from_response(self.0.get(key.into(), vec![]).await?)
+    }
+
+    pub async fn mget<T: FromValue, K: Into<Key>>(
+        self,
+        keys: impl IntoIterator<Item = K>,
+    ) -> Result<T> {
+        from_response(self.0.mget(self::keys(keys)).await?)
+    }
+
+    pub async fn set(self, key: impl Into<Key>, value: impl ToValue) -> Result<()> {
+        self.0.set(key.into(), vec![], value.to_value()).await?;
+        Ok(())
+    }
+
+    pub async fn set_ex(
+        self,
+        key: impl Into<Key>,
+        value: impl ToValue,
+        expire: Duration,
+    ) -> Result<()> {
+        self.0
+            .set_ex(key.into(), value.to_value(), seconds(expire))
+            .await?;
+        Ok(())
+    }
+
+    /// Whether the key was missing and has been set.
+    pub async fn set_nx(self, key: impl Into<Key>, value: impl ToValue) -> Result<bool> {
+        from_response(self.0.set_nx(key.into(), value.to_value()).await?)
+    }
+
+    pub async fn get_set<T: FromValue>(
+        self,
+        key: impl Into<Key>,
+        value: impl ToValue,
+    ) -> Result<T> {
+        from_response(self.0.get_set(key.into(), value.to_value()).await?)
+    }
+
+    pub async fn incr(self, key: impl Into<Key>) -> Result<i64> {
+        from_response(self.0.incr(key.into()).await?)
+    }
+
+    pub async fn incr_by(self, key: impl Into<Key>, by: i64) -> Result<i64> {
+        from_response(self.0.incr_by(key.into(), by).await?)
+    }
+
+    pub async fn decr(self, key: impl Into<Key>) -> Result<i64> {
+        from_response(self.0.decr(key.into()).await?)
+    }
+
+    pub async fn decr_by(self, key: impl Into<Key>, by: i64) -> Result<i64> {
+        from_response(self.0.decr_by(key.into(), by).await?)
+    }
+
+    //// Hash Commands ////
+
+    pub async fn hget<T: FromValue>(
+        self,
+        key: impl Into<Key>,
+        field: impl Into<String>,
+    ) -> Result<T> {
+        from_response(self.0.hget(key.into(), field.into()).await?)
+    }
+
+    /// Reads the hash as a map, such as `HashMap<String, i64>`.
+    pub async fn hget_all<T: FromValue>(self, key: impl Into<Key>) -> Result<T> {
+        let values = match self.0.hget_all(key.into()).await? {
+            ServerResponse::Bulk { values } => values,
+            other => return from_response(other),
+        };
+        // fields and values alternate
+        let mut fields = Vec::with_capacity(values.len() / 2);
+        let mut values = values.into_iter();
+        while let (Some(field), Some(value)) = (values.next(), values.next()) {
+            fields.push((String::from_value(field)?, value));
+        }
+        T::from_value(ValueType::Map(fields))
+    }
+
+    pub async fn hkeys<T: FromValue>(self, key: impl Into<Key>) -> Result<T> {
+        from_response(self.0.hkeys(key.into()).await?)
+    }
+
+    pub async fn hvalues<T: FromValue>(self, key: impl Into<Key>) -> Result<T> {
+        from_response(self.0.hvalues(key.into()).await?)
+    }
+
+    pub async fn hlen(self, key: impl Into<Key>) -> Result<usize> {
+        from_response(self.0.hlen(key.into()).await?)
+    }
+
+    pub async fn hexists(self, key: impl Into<Key>, field: impl Into<String>) -> Result<bool> {
+        from_response(self.0.hexists(key.into(), field.into()).await?)
+    }
+
+    pub async fn hset(
+        self,
+        key: impl Into<Key>,
+        field: impl Into<String>,
+        value: impl ToValue,
+    ) -> Result<()> {
+        self.0
+            .hset(key.into(), field.into(), value.to_value())
+            .await?;
+        Ok(())
+    }
+
+    /// Sets every `(field, value)` pair, such as the entries of a `HashMap`.
+    pub async fn hmset<F: Into<String>, V: ToValue>(
+        self,
+        key: impl Into<Key>,
+        fields: impl IntoIterator<Item = (F, V)>,
+    ) -> Result<()> {
+        let fields = fields
+            .into_iter()
+            .map(|(field, value)| (field.into(), value.to_value()))
+            .collect();
+        self.0.hmset(key.into(), fields).await?;
+        Ok(())
+    }
+
+    /// Number of fields removed.
+    pub async fn hdel<F: Into<String>>(
+        self,
+        key: impl Into<Key>,
+        fields: impl IntoIterator<Item = F>,
+    ) -> Result<usize> {
+        from_response(self.0.hdel(key.into(), strings(fields)).await?)
+    }
+
+    pub async fn hincr_by(
+        self,
+        key: impl Into<Key>,
+        field: impl Into<String>,
+        by: i64,
+    ) -> Result<i64> {
+        from_response(self.0.hincr_by(key.into(), field.into(), by).await?)
+    }
+
+    //// Sorted Set Commands ////
+
+    pub async fn zadd(
+        self,
+        key: impl Into<Key>,
+        score: f64,
+        member: impl Into<String>,
+    ) -> Result<()> {
+        self.0
+            .zadd(key.into(), self::score(score)?, member.into())
+            .await?;
+        Ok(())
+    }
+
+    pub async fn zscore(
+        self,
+        key: impl Into<Key>,
+        member: impl Into<String>,
+    ) -> Result<Option<f64>> {
+        from_response(self.0.zscore(key.into(), member.into()).await?)
+    }
+
+    pub async fn zrange<T: FromValue>(
+        self,
+        key: impl Into<Key>,
+        start: i32,
+        stop: i32,
+    ) -> Result<T> {
+        from_response(self.0.zrange(key.into(), start, stop).await?)
+    }
+
+    pub async fn zcard(self, key: impl Into<Key>) -> Result<usize> {
+        from_response(self.0.zcard(key.into()).await?)
+    }
+
+    //// List Commands ////
+
+    pub async fn llpush(self, key: impl Into<Key>, value: impl ToValue) -> Result<()> {
+        self.0.llpush(key.into(), value.to_value()).await?;
+        Ok(())
+    }
+
+    pub async fn lrpush(self, key: impl Into<Key>, value: impl ToValue) -> Result<()> {
+        self.0.lrpush(key.into(), value.to_value()).await?;
+        Ok(())
+    }
+
+    pub async fn llpop<T: FromValue>(self, key: impl Into<Key>, count: u32) -> Result<T> {
+        from_response(self.0.llpop(key.into(), count).await?)
+    }
+
+    pub async fn lrpop<T: FromValue>(self, key: impl Into<Key>, count: u32) -> Result<T> {
+        from_response(self.0.lrpop(key.into(), count).await?)
+    }
+
+    pub async fn lrange<T: FromValue>(
+        self,
+        key: impl Into<Key>,
+        start: u32,
+        end: u32,
+    ) -> Result<T> {
+        from_response(self.0.lrange(key.into(), start, end).await?)
+    }
+
+    //// Set Commands ////
+
+    /// Number of members added.
+    pub async fn sadd<M: Into<String>>(
+        self,
+        key: impl Into<Key>,
+        members: impl IntoIterator<Item = M>,
+    ) -> Result<usize> {
+        from_response(self.0.sadd(key.into(), strings(members)).await?)
+    }
+
+    /// Number of members removed.
+    pub async fn srem<M: Into<String>>(
+        self,
+        key: impl Into<Key>,
+        members: impl IntoIterator<Item = M>,
+    ) -> Result<usize> {
+        from_response(self.0.srem(key.into(), strings(members)).await?)
+    }
+
+    pub async fn smember(self, key: impl Into<Key>, member: impl Into<String>) -> Result<bool> {
+        from_response(self.0.smember(key.into(), member.into()).await?)
+    }
+
+    pub async fn smembers<T: FromValue>(self, key: impl Into<Key>) -> Result<T> {
+        from_response(self.0.smembers(key.into()).await?)
+    }
+
+    pub async fn scard(self, key: impl Into<Key>) -> Result<usize> {
+        from_response(self.0.scard(key.into()).await?)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<S: PacketSender<ClientCommand, ServerResponse>> Typed<S> {
+    //// Serde ////
+
+    /// Stores `value` as a hash, one field per struct field.
+    ///
+    /// Fields already in the hash but missing from `value` are left as they were.
+    pub async fn set_hash<T: Serialize + ?Sized>(
+        self,
+        key: impl Into<Key>,
+        value: &T,
+    ) -> Result<()> {
+        let fields = serde_value::to_fields(value)?;
+        self.0.hmset(key.into(), fields).await?;
+        Ok(())
+    }
+
+    /// Reads a value stored by [`Typed::set_hash`], `None` when the hash is missing.
+    pub async fn get_hash<T: DeserializeOwned>(self, key: impl Into<Key>) -> Result<Option<T>> {
+        let fields: Vec<(String, ValueType)> = self.hget_all(key).await?;
+        if fields.is_empty() {
+            return Ok(None);
+        }
+        serde_value::from_fields(fields).map(Some)
+    }
+
+    /// Stores `value` as a single binary value.
+    pub async fn set_blob<T: Serialize + ?Sized>(
+        self,
+        key: impl Into<Key>,
+        value: &T,
+    ) -> Result<()> {
+        let blob = serde_value::to_blob(value).await?;
+        self.0
+            .set(key.into(), vec![], ValueType::Bytes(blob))
+            .await?;
+        Ok(())
+    }
+
+    /// Reads a value stored by [`Typed::set_blob`], `None` when the key is missing.
+    pub async fn get_blob<T: DeserializeOwned>(self, key: impl Into<Key>) -> Result<Option<T>> {
+        match self.get::<Option<Vec<u8>>>(key).await? {
+            Some(blob) => serde_value::from_blob(&blob).await.map(Some),
+            None => Ok(None),
+        }
+    }
+}
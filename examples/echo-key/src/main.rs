@@ -6,7 +6,7 @@ async fn main() -> inferno::errors::Result<()> {
     let client_ref = &mut client;
 
     client_ref
-        .set("key".into(), ValueType::String("value".into()))
+        .set("key".into(), vec![], ValueType::String("value".into()))
         .await?;
 
     Ok(())
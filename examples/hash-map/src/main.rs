@@ -1,16 +1,16 @@
-use inferno::driver::{Client, CommandSender, Instruction, ServerResponse, ValueType};
+use inferno::driver::prelude::*;
 
 #[tokio::main]
 async fn main() -> inferno::errors::Result<()> {
     let mut client = Client::connect("127.0.0.1:3599").await?;
 
     client
-        .del("key", vec![Instruction::hash("hash-key")])
+        .del("key".into(), vec![Instruction::hash("hash-key")])
         .await?;
 
     client
         .set(
-            "key",
+            "key".into(),
             vec![Instruction::hash("hash-key"), Instruction::hash("nested")],
             ValueType::UInt(1),
         )
@@ -18,7 +18,7 @@ async fn main() -> inferno::errors::Result<()> {
 
     let value = client
         .get(
-            "key",
+            "key".into(),
             vec![Instruction::hash("hash-key"), Instruction::hash("nested")],
         )
         .await?;
@@ -27,7 +27,7 @@ async fn main() -> inferno::errors::Result<()> {
 
     assert!(matches!(
         value,
-        ServerResponse::OkSingle {
+        ServerResponse::Single {
             value: ValueType::UInt(1)
         }
     ));
@@ -1,134 +1,246 @@
 use crate::state::State;
-use packets::{ClientCommand, Packet, ServerResponse};
+use packets::envelope::Envelope;
+use packets::frame;
+use packets::handshake::Hello;
+use packets::key::Key;
+use packets::{ClientCommand, ServerResponse};
+use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::net::TcpStream;
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::sync::{watch, Semaphore};
 use tokio::task::JoinHandle;
 
+/// Commands running or waiting to reply, further frames are left unread until one finishes.
+const MAX_IN_FLIGHT: usize = 128;
+
 pub fn handle(
     state: State,
     stream: TcpStream,
     _addr: SocketAddr,
+    max_frame_size: u32,
 ) -> JoinHandle<errors::Result<()>> {
-    tokio::spawn(async move { inner_handle(state, stream).await })
+    tokio::spawn(async move { inner_handle(state, stream, max_frame_size).await })
+}
+
+async fn inner_handle(
+    state: State,
+    mut stream: TcpStream,
+    max_frame_size: u32,
+) -> errors::Result<()> {
+    // replies are small and latency bound, they shouldn't wait to be coalesced
+    stream.set_nodelay(true)?;
+    let hello = handshake(&mut stream, max_frame_size).await?;
+    log::debug!("Negotiated {:?}", hello);
+
+    let (read, write) = stream.into_split();
+    let (responses, pending) = mpsc::channel(MAX_IN_FLIGHT);
+    let writer = tokio::spawn(write_responses(write, pending));
+
+    let read_result = read_requests(state, read, responses, max_frame_size).await;
+    // commands still running keep the writer alive until they have replied
+    let write_result = writer.await.unwrap_or(Ok(()));
+    read_result.and(write_result)
 }
 
-async fn inner_handle(state: State, stream: TcpStream) -> errors::Result<()> {
-    let (mut read, mut write) = stream.into_split();
+// the reply always carries our own version, so a mismatched client can report it too
+async fn handshake(stream: &mut TcpStream, max_frame_size: u32) -> errors::Result<Hello> {
+    let remote = frame::read_packet::<Hello, _>(stream, max_frame_size).await?;
+    let local = Hello::default();
+    let agreed = local.negotiate(&remote);
+    let reply = agreed.as_ref().map_or(local, |agreed| *agreed);
+    frame::write_packet(stream, &reply).await?;
+    agreed
+}
+
+// every command runs on its own task, so responses go out in the order commands finish; a
+// command only waits for the ones read before it which touch the same keys
+async fn read_requests(
+    state: State,
+    mut read: OwnedReadHalf,
+    responses: Sender<Envelope<ServerResponse>>,
+    max_frame_size: u32,
+) -> errors::Result<()> {
+    let in_flight = Arc::new(Semaphore::new(MAX_IN_FLIGHT));
+    // the latest command read for each key, closed once it has replied
+    let mut latest = HashMap::<Key, watch::Receiver<()>>::new();
     loop {
-        let command = ClientCommand::read(&mut read).await?;
-
-        log::info!("Command: {:?}", command);
-
-        macro_rules! response_handler {
-            ($command:ident($state:ident) -> { $($big:ident as $small:ident { $($field:ident),*$(,)? })* } -> $return_ident:ident) => {
-                use packets::ClientCommandExecutor;
-                let $return_ident = match $command {
-                    $(
-                        ClientCommand::$big { $($field),* } => $state.$small($($field),*).await,
-                    )*
-                };
+        let Envelope {
+            id,
+            packet: command,
+        } = frame::read_packet::<Envelope<ClientCommand>, _>(&mut read, max_frame_size).await?;
+
+        log::info!("Command {}: {:?}", id, command);
+
+        // a client sending faster than it reads is held back here
+        let Ok(permit) = in_flight.clone().acquire_owned().await else {
+            return Ok(());
+        };
+
+        latest.retain(|_, finished| finished.has_changed().is_ok());
+        let (replied, finished) = watch::channel(());
+        let earlier = command
+            .keys()
+            .into_iter()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .filter_map(|key| latest.insert(key.clone(), finished.clone()))
+            .collect::<Vec<_>>();
+
+        let state = state.clone();
+        let responses = responses.clone();
+        tokio::spawn(async move {
+            for mut earlier in earlier {
+                let _ = earlier.changed().await;
             }
-        }
+            let response = execute(state, command)
+                .await
+                .unwrap_or_else(|err| ServerResponse::Error { err });
+            // only fails once the writer has given up on the connection
+            let _ = responses.send(Envelope::new(id, response)).await;
+            drop((replied, permit));
+        });
+    }
+}
 
-        response_handler!(
-            command(state) -> {
-                Expire as expire { key, expire }
-                Persist as persist { key }
-                Ttl as ttl { key }
-                Del as del { keys }
-
-                //// Value Commands ////
-
-                Decr as decr { key }
-                DecrBy as decr_by { key, by }
-                Incr as incr { key }
-                IncrBy as incr_by { key, by }
-
-                Get as get { key }
-                GetDel as get_del { keys }
-                GetEx as get_ex { key, expire }
-                GetSet as get_set { key, value }
-                MGet as mget { keys }
-
-                Set as set { key, value }
-                SetEx as set_ex { key, value, expire }
-                SetNx as set_nx { key, value }
-                MSet as mset { keys, values }
-                MSetNx as mset_nx { keys, values }
-
-                //// Hash Commands ////
-
-                HExpire as hexpire { key, field, expire }
-                HDel as hdel { key, fields }
-                HDelGet as hdel_get { key, fields }
-                HPopRand as hpop_rand { key, count }
-
-                HExists as hexists { key, field }
-                HGet as hget { key, field }
-                HGetAll as hget_all { key }
-                HMGet as hmget { key, fields }
-                HKeys as hkeys { key }
-                HValues as hvalues { key }
-                HLen as hlen { key }
-
-                HDecr as hdecr { key, field }
-                HDecrBy as hdecr_by { key, field }
-                HIncr as hincr { key, field }
-                HIncrBy as hincr_by { key, field, by }
-
-                HSet as hset { key, field, value }
-                HSetNx as hset_nx { key, field, value }
-                HSetEx as hset_ex { key, field, value, expire }
-                HMSet as hmset { key, fields }
-                HMSetNx as hmset_nx { key, fields }
-
-                //// Set Commands ////
-
-                ZAdd as zadd { key, score, member }
-                ZAddNx as zadd_nx { key, score, member }
-                ZIncrBy as zincr_by { key, score, member }
-                ZDecrBy as zdecr_by { key, score, member }
-
-                ZScore as zscore { key, member }
-                ZMScore as zmscore { key, members }
-
-                ZPopMin as zpop_min { key, count }
-                ZPopMax as zpop_max { key, count }
-
-                ZRem as zrem { key, member }
-                ZExpire as zexpire { key, member, expire }
-
-                //// List Commands ////
-
-                LLPush as llpush { key, value }
-                LLPushNx as llpush_nx { key, value }
-                LLPushEx as llpush_ex { key, value, expire }
-                LRPush as lrpush { key, value }
-                LRPushNx as lrpush_nx { key, value }
-                LRPushEx as lrpush_ex { key, value, expire }
-
-                LExpire as lexpire { key, index, expire }
-                LLPop as llpop { key, count }
-                LRPop as lrpop { key, count }
-                LRange as lrange { key, start, end }
-
-                //// Set Commands ////
-
-                SAdd as sadd { key, members }
-                SAddNx as sadd_nx { key, members }
-                SAddEx as sadd_ex { key, member, expire }
-                SMember as smember { key, member }
-                SMembers as smembers { key }
-
-                SExpire as sexpire { key, member, expire }
-                SRem as srem { key, members }
-                SPop as spop { key, count }
-            } -> response
-        );
-
-        match response {
-            Ok(response) => response.write(&mut write).await?,
-            Err(err) => ServerResponse::Error { err }.write(&mut write).await?,
+async fn write_responses(
+    mut write: OwnedWriteHalf,
+    mut pending: Receiver<Envelope<ServerResponse>>,
+) -> errors::Result<()> {
+    while let Some(response) = pending.recv().await {
+        frame::write_packet(&mut write, &response).await?;
+    }
+    Ok(())
+}
+
+pub(crate) async fn execute(
+    state: State,
+    command: ClientCommand,
+) -> errors::Result<ServerResponse> {
+    macro_rules! response_handler {
+        ($command:ident($state:ident) -> { $($big:ident as $small:ident { $($field:ident),*$(,)? })* } -> $return_ident:ident) => {
+            use packets::ClientCommandExecutor;
+            let $return_ident = match $command {
+                $(
+                    ClientCommand::$big { $($field),* } => $state.$small($($field),*).await,
+                )*
+            };
         }
     }
+
+    response_handler!(
+        command(state) -> {
+            Expire as expire { key, expire }
+            Persist as persist { key }
+            Ttl as ttl { key }
+            Del as del { key, path }
+            Ping as ping {}
+
+            //// Value Commands ////
+
+            Decr as decr { key }
+            DecrBy as decr_by { key, by }
+            Incr as incr { key }
+            IncrBy as incr_by { key, by }
+
+            Get as get { key, path }
+            GetDel as get_del { keys }
+            GetEx as get_ex { key, expire }
+            GetSet as get_set { key, value }
+            MGet as mget { keys }
+
+            Set as set { key, path, value }
+            SetEx as set_ex { key, value, expire }
+            SetNx as set_nx { key, value }
+            MSet as mset { keys, values }
+            MSetNx as mset_nx { keys, values }
+
+            //// Hash Commands ////
+
+            HExpire as hexpire { key, field, expire }
+            HDel as hdel { key, fields }
+            HDelGet as hdel_get { key, fields }
+            HPopRand as hpop_rand { key, count }
+
+            HExists as hexists { key, field }
+            HGet as hget { key, field }
+            HGetAll as hget_all { key }
+            HMGet as hmget { key, fields }
+            HKeys as hkeys { key }
+            HValues as hvalues { key }
+            HLen as hlen { key }
+
+            HDecr as hdecr { key, field }
+            HDecrBy as hdecr_by { key, field, by }
+            HIncr as hincr { key, field }
+            HIncrBy as hincr_by { key, field, by }
+
+            HSet as hset { key, field, value }
+            HSetNx as hset_nx { key, field, value }
+            HSetEx as hset_ex { key, field, value, expire }
+            HMSet as hmset { key, fields }
+            HMSetNx as hmset_nx { key, fields }
+
+            //// Sorted Set Commands ////
+
+            ZAdd as zadd { key, score, member }
+            ZAddNx as zadd_nx { key, score, member }
+            ZIncrBy as zincr_by { key, by, member }
+            ZDecrBy as zdecr_by { key, by, member }
+
+            ZScore as zscore { key, member }
+            ZMScore as zmscore { key, members }
+            ZRange as zrange { key, start, stop }
+            ZRangeByScore as zrange_by_score { key, min, max }
+            ZRank as zrank { key, member }
+            ZCard as zcard { key }
+            ZCount as zcount { key, min, max }
+
+            ZPopMin as zpop_min { key, count }
+            ZPopMax as zpop_max { key, count }
+
+            ZRem as zrem { key, member }
+            ZExpire as zexpire { key, member, expire }
+
+            //// List Commands ////
+
+            LLPush as llpush { key, value }
+            LLPushNx as llpush_nx { key, value }
+            LLPushEx as llpush_ex { key, value, expire }
+            LRPush as lrpush { key, value }
+            LRPushNx as lrpush_nx { key, value }
+            LRPushEx as lrpush_ex { key, value, expire }
+
+            LExpire as lexpire { key, index, expire }
+            LLPop as llpop { key, count }
+            LRPop as lrpop { key, count }
+            LRange as lrange { key, start, end }
+
+            //// Set Commands ////
+
+            SAdd as sadd { key, members }
+            SAddNx as sadd_nx { key, members }
+            SAddEx as sadd_ex { key, member, expire }
+            SMember as smember { key, member }
+            SMembers as smembers { key }
+
+            SExpire as sexpire { key, member, expire }
+            SRem as srem { key, members }
+            SPop as spop { key, count }
+
+            SCard as scard { key }
+            SRandMember as srand_member { key, count }
+            SMove as smove { source, destination, member }
+            SUnion as sunion { keys }
+            SInter as sinter { keys }
+            SDiff as sdiff { keys }
+            SUnionStore as sunion_store { destination, keys }
+            SInterStore as sinter_store { destination, keys }
+            SDiffStore as sdiff_store { destination, keys }
+        } -> response
+    );
+
+    response
 }
@@ -1,26 +1,44 @@
+use crate::data::expiring::Expiring;
 use crate::state::CompositeValue;
 use dashmap::DashMap;
+use errors::Result;
 use packets::value::ValueType;
 use std::sync::Arc;
+use std::time::Instant;
 
 pub trait Container {
-    fn ct_get(&self, key: &str) -> Option<ValueType>;
+    fn ct_contains(&self, key: &str, now: Instant) -> bool;
 
-    fn ct_insert(&self, key: &str, value: ValueType) -> Option<ValueType>;
+    fn ct_get(&self, key: &str, now: Instant) -> Result<Option<ValueType>>;
 
-    fn ct_remove(&self, key: &str) -> Option<ValueType>;
+    /// Returns whether `key` was not already present.
+    fn ct_insert(&self, key: &str, value: CompositeValue, now: Instant) -> bool;
+
+    fn ct_remove(&self, key: &str, now: Instant) -> Result<Option<ValueType>>;
 }
 
-impl Container for Arc<DashMap<String, ValueType>> {
-    fn ct_get(&self, key: &str) -> Option<ValueType> {
-        DashMap::get(self, key).map(|value| value.value().clone())
+// fields past their deadline are treated as absent
+impl Container for Arc<DashMap<String, Expiring<CompositeValue>>> {
+    fn ct_contains(&self, key: &str, now: Instant) -> bool {
+        DashMap::get(self, key).is_some_and(|value| value.is_live(now))
+    }
+
+    fn ct_get(&self, key: &str, now: Instant) -> Result<Option<ValueType>> {
+        DashMap::get(self, key)
+            .filter(|value| value.is_live(now))
+            .map(|value| value.value().value().to_value(now))
+            .transpose()
     }
 
-    fn ct_insert(&self, key: &str, value: ValueType) -> Option<ValueType> {
-        DashMap::insert(self, key.to_string(), value).map(|value| value.clone())
+    fn ct_insert(&self, key: &str, value: CompositeValue, now: Instant) -> bool {
+        DashMap::insert(self, key.to_string(), Expiring::new(value, None))
+            .is_none_or(|old| !old.is_live(now))
     }
 
-    fn ct_remove(&self, key: &str) -> Option<ValueType> {
-        DashMap::remove(self, key).map(|(_, value)| value.clone())
+    fn ct_remove(&self, key: &str, now: Instant) -> Result<Option<ValueType>> {
+        DashMap::remove(self, key)
+            .filter(|(_, value)| value.is_live(now))
+            .map(|(_, value)| value.into_value().to_value(now))
+            .transpose()
     }
 }
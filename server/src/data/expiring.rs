@@ -0,0 +1,56 @@
+//! Collection Members With Deadlines
+
+use std::fmt::Debug;
+use std::sync::{Mutex, PoisonError};
+use std::time::Instant;
+
+// the deadline is behind a lock so members reachable only through shared references (list nodes,
+// tree values) can still have it changed in place
+pub struct Expiring<T> {
+    value: T,
+    deadline: Mutex<Option<Instant>>,
+}
+
+impl<T> Expiring<T> {
+    pub fn new(value: T, deadline: Option<Instant>) -> Self {
+        Self {
+            value,
+            deadline: Mutex::new(deadline),
+        }
+    }
+
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    pub fn into_value(self) -> T {
+        self.value
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        *self.deadline.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn set_deadline(&self, deadline: Option<Instant>) {
+        *self.deadline.lock().unwrap_or_else(PoisonError::into_inner) = deadline;
+    }
+
+    pub fn is_live(&self, now: Instant) -> bool {
+        self.deadline().is_none_or(|deadline| deadline > now)
+    }
+}
+
+impl<T: Clone> Clone for Expiring<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.value.clone(), self.deadline())
+    }
+}
+
+impl<T: Debug> Debug for Expiring<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Expiring")
+            .field("value", &self.value)
+            .field("deadline", &self.deadline())
+            .finish()
+    }
+}
@@ -2,6 +2,7 @@
 
 use std::fmt::Debug;
 use std::mem::{ManuallyDrop, MaybeUninit};
+use std::ops::Deref;
 use std::sync::atomic::{AtomicBool, AtomicPtr};
 use std::sync::{Arc, RwLock};
 
@@ -237,6 +238,72 @@ where
     }
 }
 
+impl<T> ArcSwapLinkedList<T>
+where
+    T: Send + Sync,
+{
+    /// Walks the list from front to back. Nodes popped while iterating are still visited, nodes
+    /// pushed while iterating may or may not be.
+    pub fn iter(&self) -> Iter<T> {
+        Iter {
+            next: self.head.load_full(),
+        }
+    }
+
+    /// Drops every value `keep` rejects, see [`ArcSwapLinkedList::rebuild`].
+    pub fn retain(&self, keep: impl FnMut(&T) -> bool)
+    where
+        T: Clone,
+    {
+        self.rebuild(|values| values.retain(keep));
+    }
+
+    /// Drains the list, lets `edit` change the values in order and pushes them back. Anything
+    /// pushed or popped concurrently can be lost so the caller must have exclusive use of the list.
+    pub fn rebuild<R>(&self, edit: impl FnOnce(&mut Vec<T>) -> R) -> R
+    where
+        T: Clone,
+    {
+        let mut values = Vec::new();
+        while let Some(value) = self.pop_front() {
+            values.push(value);
+        }
+        let result = edit(&mut values);
+        for value in values {
+            self.push_back(value);
+        }
+        result
+    }
+}
+
+pub struct Iter<T> {
+    next: Arc<Option<ArcSwapListNode<T>>>,
+}
+
+impl<T> Iterator for Iter<T> {
+    type Item = ListItem<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = Arc::clone(&self.next);
+        self.next = current.as_ref().as_ref()?.next.load_full();
+        Some(ListItem(current))
+    }
+}
+
+// keeps the node alive for as long as the value is borrowed
+pub struct ListItem<T>(Arc<Option<ArcSwapListNode<T>>>);
+
+impl<T> Deref for ListItem<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        let Some(node) = self.0.as_ref() else {
+            unreachable!()
+        };
+        &node.value
+    }
+}
+
 #[derive(Debug)]
 struct ArcSwapListNode<T> {
     prior: ArcSwap<Option<ArcSwapListNode<T>>>,
@@ -317,6 +384,27 @@ mod tests {
         assert_eq!(list.pop_front(), Some(3));
     }
 
+    #[test]
+    pub fn test_iter() {
+        let list = ArcSwapLinkedList::default();
+        list.push_back(1);
+        list.push_back(2);
+        list.push_front(0);
+        let values = list.iter().map(|value| *value).collect::<Vec<usize>>();
+        assert_eq!(values, vec![0, 1, 2]);
+    }
+
+    #[test]
+    pub fn test_retain() {
+        let list = ArcSwapLinkedList::default();
+        for i in 0..10 {
+            list.push_back(i);
+        }
+        list.retain(|value| value % 2 == 0);
+        let values = list.iter().map(|value| *value).collect::<Vec<usize>>();
+        assert_eq!(values, vec![0, 2, 4, 6, 8]);
+    }
+
     #[rstest]
     pub fn test_multi_threaded_push<L: LikeLinkedList<Item = usize> + 'static>(as_default: L) {
         let _ = as_default;
@@ -1 +1,3 @@
+pub mod expiring;
 pub mod list;
+pub mod sorted_set;
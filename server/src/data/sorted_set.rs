@@ -0,0 +1,327 @@
+//! Concurrent Sorted Set Implementation
+
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Bound;
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Instant;
+
+use packets::score::Score;
+
+use crate::data::expiring::Expiring;
+
+// members are indexed twice, by name for lookups and by (score, name) for ordered access, the
+// lock keeps both indexes in step
+#[derive(Default)]
+pub struct SortedSet {
+    inner: RwLock<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    scores: HashMap<String, Expiring<Score>>,
+    order: BTreeSet<(Score, String)>,
+}
+
+impl Inner {
+    fn live_score(&self, member: &str, now: Instant) -> Option<Score> {
+        self.scores
+            .get(member)
+            .filter(|score| score.is_live(now))
+            .map(|score| *score.value())
+    }
+
+    fn live_order(&self, now: Instant) -> impl DoubleEndedIterator<Item = &(Score, String)> + '_ {
+        self.order
+            .iter()
+            .filter(move |(_, member)| self.live_score(member, now).is_some())
+    }
+
+    fn insert(&mut self, member: String, score: Score, deadline: Option<Instant>) {
+        if let Some(old) = self.scores.remove(&member) {
+            self.order.remove(&(*old.value(), member.clone()));
+        }
+        self.order.insert((score, member.clone()));
+        self.scores.insert(member, Expiring::new(score, deadline));
+    }
+
+    fn remove(&mut self, member: &str) -> Option<Expiring<Score>> {
+        let score = self.scores.remove(member)?;
+        self.order.remove(&(*score.value(), member.to_string()));
+        Some(score)
+    }
+}
+
+impl SortedSet {
+    fn read(&self) -> RwLockReadGuard<'_, Inner> {
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Inner> {
+        self.inner.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Sets the score of `member`, returning whether it was not already present.
+    pub fn insert(
+        &self,
+        member: String,
+        score: Score,
+        deadline: Option<Instant>,
+        now: Instant,
+    ) -> bool {
+        let mut inner = self.write();
+        let created = inner.live_score(&member, now).is_none();
+        inner.insert(member, score, deadline);
+        created
+    }
+
+    pub fn insert_nx(&self, member: String, score: Score, now: Instant) -> bool {
+        let mut inner = self.write();
+        if inner.live_score(&member, now).is_some() {
+            return false;
+        }
+        inner.insert(member, score, None);
+        true
+    }
+
+    /// Adds to the score of `member`, treating a missing member as a score of 0. Returns `None`
+    /// when the new score would be NaN.
+    pub fn incr(&self, member: String, by: Score, now: Instant) -> Option<Score> {
+        let mut inner = self.write();
+        let (current, deadline) = match inner.scores.get(&member) {
+            Some(score) if score.is_live(now) => (*score.value(), score.deadline()),
+            _ => (Score::default(), None),
+        };
+        let updated = current.checked_add(by)?;
+        inner.insert(member, updated, deadline);
+        Some(updated)
+    }
+
+    pub fn score(&self, member: &str, now: Instant) -> Option<Score> {
+        self.read().live_score(member, now)
+    }
+
+    pub fn remove(&self, member: &str, now: Instant) -> Option<Score> {
+        self.write()
+            .remove(member)
+            .filter(|score| score.is_live(now))
+            .map(Expiring::into_value)
+    }
+
+    pub fn expire(&self, member: &str, deadline: Instant, now: Instant) -> bool {
+        let inner = self.read();
+        match inner.scores.get(member) {
+            Some(score) if score.is_live(now) => {
+                score.set_deadline(Some(deadline));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn pop_min(&self, count: usize, now: Instant) -> Vec<(String, Score)> {
+        self.pop(count, now, false)
+    }
+
+    pub fn pop_max(&self, count: usize, now: Instant) -> Vec<(String, Score)> {
+        self.pop(count, now, true)
+    }
+
+    fn pop(&self, count: usize, now: Instant, from_max: bool) -> Vec<(String, Score)> {
+        let mut inner = self.write();
+        let popped = if from_max {
+            inner
+                .live_order(now)
+                .rev()
+                .take(count)
+                .cloned()
+                .collect::<Vec<_>>()
+        } else {
+            inner
+                .live_order(now)
+                .take(count)
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        popped
+            .into_iter()
+            .map(|(score, member)| {
+                inner.remove(&member);
+                (member, score)
+            })
+            .collect()
+    }
+
+    /// Members ranked `start..=stop` in ascending score order, negative ranks count back from the
+    /// highest score.
+    pub fn range(&self, start: i32, stop: i32, now: Instant) -> Vec<(String, Score)> {
+        let inner = self.read();
+        let len = inner.live_order(now).count() as i64;
+        let resolve = |rank: i32| {
+            let rank = rank as i64;
+            if rank < 0 {
+                (len + rank).max(0)
+            } else {
+                rank
+            }
+        };
+        let (start, stop) = (resolve(start), resolve(stop).min(len - 1));
+        if start > stop {
+            return vec![];
+        }
+        inner
+            .live_order(now)
+            .skip(start as usize)
+            .take((stop - start + 1) as usize)
+            .map(|(score, member)| (member.clone(), *score))
+            .collect()
+    }
+
+    /// Members scoring within `min..=max` in ascending score order.
+    pub fn range_by_score(&self, min: Score, max: Score, now: Instant) -> Vec<(String, Score)> {
+        if min > max {
+            return vec![];
+        }
+        let inner = self.read();
+        inner
+            .order
+            .range((Bound::Included((min, String::new())), Bound::Unbounded))
+            .take_while(|(score, _)| *score <= max)
+            .filter(|(_, member)| inner.live_score(member, now).is_some())
+            .map(|(score, member)| (member.clone(), *score))
+            .collect()
+    }
+
+    pub fn count(&self, min: Score, max: Score, now: Instant) -> usize {
+        self.range_by_score(min, max, now).len()
+    }
+
+    /// Position of `member` in ascending score order, found by walking the ordered index.
+    pub fn rank(&self, member: &str, now: Instant) -> Option<usize> {
+        let inner = self.read();
+        let score = inner.live_score(member, now)?;
+        let target = (score, member.to_string());
+        Some(
+            inner
+                .order
+                .range(..target)
+                .filter(|(_, member)| inner.live_score(member, now).is_some())
+                .count(),
+        )
+    }
+
+    pub fn len(&self, now: Instant) -> usize {
+        self.read().live_order(now).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read().scores.is_empty()
+    }
+
+    pub fn has_live(&self, now: Instant) -> bool {
+        self.read().live_order(now).next().is_some()
+    }
+
+    /// Drops `member` if its deadline has passed.
+    pub fn remove_expired(&self, member: &str, now: Instant) {
+        let mut inner = self.write();
+        if inner
+            .scores
+            .get(member)
+            .is_some_and(|score| !score.is_live(now))
+        {
+            inner.remove(member);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SortedSet;
+    use packets::score::Score;
+    use std::time::{Duration, Instant};
+
+    fn with_members() -> SortedSet {
+        let set = SortedSet::default();
+        let now = Instant::now();
+        set.insert("b".into(), 2.into(), None, now);
+        set.insert("a".into(), 1.into(), None, now);
+        set.insert("c".into(), 3.into(), None, now);
+        set
+    }
+
+    #[test]
+    pub fn test_reinsert_moves_member() {
+        let set = with_members();
+        let now = Instant::now();
+        assert!(!set.insert("a".into(), 4.into(), None, now));
+        assert_eq!(set.len(now), 3);
+        assert_eq!(set.rank("a", now), Some(2));
+        assert_eq!(set.score("a", now), Some(4.into()));
+    }
+
+    #[test]
+    pub fn test_pop_order() {
+        let set = with_members();
+        let now = Instant::now();
+        assert_eq!(set.pop_min(1, now), vec![("a".to_string(), 1.into())]);
+        assert_eq!(
+            set.pop_max(5, now),
+            vec![("c".to_string(), 3.into()), ("b".to_string(), 2.into())]
+        );
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    pub fn test_ranges() {
+        let set = with_members();
+        let now = Instant::now();
+        let members = |range: Vec<(String, Score)>| {
+            range
+                .into_iter()
+                .map(|(member, _)| member)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(members(set.range(0, -1, now)), vec!["a", "b", "c"]);
+        assert_eq!(members(set.range(-2, 10, now)), vec!["b", "c"]);
+        assert_eq!(members(set.range(2, 1, now)), Vec::<String>::new());
+        assert_eq!(
+            members(set.range_by_score(2.into(), 3.into(), now)),
+            vec!["b", "c"]
+        );
+        assert_eq!(set.count(Score::MIN, 1.into(), now), 1);
+    }
+
+    #[test]
+    pub fn test_float_scores() {
+        let set = SortedSet::default();
+        let now = Instant::now();
+        let score = |value: f64| Score::new(value).unwrap();
+        set.insert("inf".into(), Score::MAX, None, now);
+        set.insert("half".into(), score(0.5), None, now);
+        set.insert("neg".into(), score(-0.0), None, now);
+        set.insert("zero".into(), score(0.0), None, now);
+
+        assert_eq!(set.count(score(0.0), score(0.0), now), 2);
+        assert_eq!(set.rank("half", now), Some(2));
+        assert_eq!(set.incr("half".into(), score(0.25), now), Some(score(0.75)));
+        assert_eq!(set.incr("inf".into(), Score::MIN, now), None);
+        assert_eq!(set.score("inf", now), Some(Score::MAX));
+    }
+
+    #[test]
+    pub fn test_expired_members_hidden() {
+        let set = with_members();
+        let now = Instant::now();
+        assert!(set.expire("a", now + Duration::from_secs(1), now));
+
+        let later = now + Duration::from_secs(1);
+        assert_eq!(set.score("a", later), None);
+        assert_eq!(set.rank("b", later), Some(0));
+        assert_eq!(set.len(later), 2);
+
+        set.remove_expired("a", later);
+        set.remove_expired("b", later);
+        assert_eq!(set.len(now), 2);
+        assert!(set.has_live(later));
+    }
+}
@@ -1,2 +1,6 @@
+pub mod connection;
+mod container;
 pub mod data;
+pub mod expiry;
+pub mod resp;
 pub mod state;
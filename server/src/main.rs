@@ -1,10 +1,8 @@
-mod connection;
-mod container;
-pub(crate) mod data;
-mod state;
-
-use crate::state::State;
 use errors::Result;
+use packets::frame;
+use server::state::State;
+use server::{connection, expiry, resp};
+use std::time::Duration;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -13,6 +11,14 @@ async fn main() -> Result<()> {
     let stream = tokio::net::TcpListener::bind("127.0.0.1:3599").await?;
 
     let state = State::default();
+    expiry::spawn_sweeper(state.clone(), Duration::from_millis(100));
+
+    // redis protocol listener for ops tooling, only when asked for
+    if let Ok(addr) = std::env::var("INFERNO_RESP_ADDR") {
+        let listener = tokio::net::TcpListener::bind(&addr).await?;
+        log::info!("...Accepting RESP connections on {}...", addr);
+        tokio::spawn(accept_resp(state.clone(), listener));
+    }
 
     log::info!("...Accepting connections...");
 
@@ -22,6 +28,15 @@ async fn main() -> Result<()> {
         let (stream, addr) = stream.accept().await?;
 
         log::debug!("New connection from {}", addr);
-        connection::handle(state, stream, addr);
+        connection::handle(state, stream, addr, frame::DEFAULT_MAX_FRAME_SIZE);
+    }
+}
+
+async fn accept_resp(state: State, listener: tokio::net::TcpListener) -> Result<()> {
+    loop {
+        let (stream, addr) = listener.accept().await?;
+
+        log::debug!("New RESP connection from {}", addr);
+        resp::handle(state.clone(), stream, addr);
     }
 }
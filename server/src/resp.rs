@@ -0,0 +1,760 @@
+use crate::connection::execute;
+use crate::state::State;
+use errors::{PacketsError, Result};
+use packets::frame::{MAX_BYTES_LENGTH, MAX_COLLECTION_LENGTH};
+use packets::key::Key;
+use packets::score::Score;
+use packets::value::ValueType;
+use packets::{ClientCommand, ServerResponse};
+use std::net::SocketAddr;
+use std::str::FromStr;
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+
+// longest header or inline command line accepted
+const MAX_LINE_LENGTH: u64 = 64 * 1024;
+
+pub fn handle(state: State, stream: TcpStream, _addr: SocketAddr) -> JoinHandle<Result<()>> {
+    tokio::spawn(async move { serve(state, stream).await })
+}
+
+/// Answers Redis protocol requests on `stream` until the peer hangs up or sends `QUIT`.
+pub async fn serve<S>(state: State, stream: S) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (read, mut write) = tokio::io::split(stream);
+    let mut read = BufReader::new(read);
+    let mut version = Version::Resp2;
+
+    loop {
+        let args = match read_request(&mut read).await {
+            Ok(Some(args)) => args,
+            Ok(None) => return Ok(()),
+            Err(err) => {
+                // like redis, report what was wrong before dropping the connection
+                let reply = Frame::Error(format!("ERR Protocol error: {}", err));
+                write.write_all(&reply.encode(version)).await?;
+                return Err(err);
+            }
+        };
+        if args.is_empty() {
+            continue;
+        }
+
+        log::info!("RESP command: {:?}", String::from_utf8_lossy(&args[0]));
+
+        let reply = match translate(args) {
+            Ok(Action::Dispatch(commands, reply)) => {
+                let mut responses = Vec::with_capacity(commands.len());
+                for command in commands {
+                    responses.push(execute(state.clone(), command).await);
+                }
+                reply.build(responses)
+            }
+            Ok(Action::Batch(batch)) => batch.apply(&state),
+            Ok(Action::Ttl(key)) => ttl_reply(&state, key).await,
+            Ok(Action::Reply(frame)) => frame,
+            Ok(Action::Hello(requested)) => {
+                version = requested;
+                hello_reply(version)
+            }
+            Ok(Action::Quit) => {
+                write
+                    .write_all(&Frame::Simple("OK".into()).encode(version))
+                    .await?;
+                return Ok(());
+            }
+            Err(err) => err,
+        };
+        write.write_all(&reply.encode(version)).await?;
+    }
+}
+
+// seconds left, `-1` when the key has no deadline and `-2` when it is missing; the native reply
+// is `None` for both, so the key is looked up again to tell them apart
+async fn ttl_reply(state: &State, key: Key) -> Frame {
+    match execute(state.clone(), ClientCommand::Ttl { key: key.clone() }).await {
+        Ok(ServerResponse::OptInt { value: Some(value) }) => Frame::Integer(value.into()),
+        Ok(ServerResponse::OptInt { value: None }) if state.contains_key(&key) => {
+            Frame::Integer(-1)
+        }
+        Ok(ServerResponse::OptInt { value: None }) => Frame::Integer(-2),
+        Ok(response) => Frame::from(response),
+        Err(err) => Frame::from(ServerResponse::Error { err }),
+    }
+}
+
+/// Reads one request as its arguments, `None` once the peer has closed the connection.
+async fn read_request<R>(read: &mut R) -> Result<Option<Vec<Vec<u8>>>>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let Some(line) = read_line(read).await? else {
+        return Ok(None);
+    };
+    let Some(count) = line.strip_prefix(b"*") else {
+        // inline commands are split on whitespace, as typed into telnet
+        let args = line
+            .split(|byte| byte.is_ascii_whitespace())
+            .filter(|arg| !arg.is_empty())
+            .map(<[u8]>::to_vec)
+            .collect();
+        return Ok(Some(args));
+    };
+
+    let count = parse_length(count, MAX_COLLECTION_LENGTH)?;
+    let mut args = Vec::with_capacity(count.min(64));
+    for _ in 0..count {
+        let line = read_line(read)
+            .await?
+            .ok_or(PacketsError::MalformedResp("unexpected end of request"))?;
+        let length = line
+            .strip_prefix(b"$")
+            .ok_or(PacketsError::MalformedResp("expected bulk string"))?;
+        let length = parse_length(length, MAX_BYTES_LENGTH)?;
+
+        let mut arg = Vec::new();
+        read.take(length as u64 + 2).read_to_end(&mut arg).await?;
+        if !arg.ends_with(b"\r\n") || arg.len() != length + 2 {
+            return Err(PacketsError::MalformedResp("bulk string length mismatch").into());
+        }
+        arg.truncate(length);
+        args.push(arg);
+    }
+    Ok(Some(args))
+}
+
+async fn read_line<R>(read: &mut R) -> Result<Option<Vec<u8>>>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut line = Vec::new();
+    read.take(MAX_LINE_LENGTH)
+        .read_until(b'\n', &mut line)
+        .await?;
+    if line.is_empty() {
+        return Ok(None);
+    }
+    if !line.ends_with(b"\r\n") {
+        return Err(PacketsError::MalformedResp("line too long or not terminated").into());
+    }
+    line.truncate(line.len() - 2);
+    Ok(Some(line))
+}
+
+fn parse_length(digits: &[u8], limit: u32) -> Result<usize> {
+    let length = std::str::from_utf8(digits)
+        .ok()
+        .and_then(|digits| digits.parse::<u32>().ok())
+        .ok_or(PacketsError::MalformedResp("invalid length"))?;
+    if length > limit {
+        return Err(PacketsError::LengthExceeded {
+            length: length as usize,
+            limit: limit as usize,
+        }
+        .into());
+    }
+    Ok(length as usize)
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Version {
+    Resp2,
+    Resp3,
+}
+
+/// A RESP reply, RESP3 only types are downgraded when talking RESP2.
+#[derive(Debug, Clone, PartialEq)]
+enum Frame {
+    Simple(String),
+    Error(String),
+    Integer(i64),
+    Bulk(Vec<u8>),
+    Null,
+    Array(Vec<Frame>),
+    Map(Vec<(Frame, Frame)>),
+    Double(f64),
+    Boolean(bool),
+}
+
+impl Frame {
+    fn encode(&self, version: Version) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode_into(&mut out, version);
+        out
+    }
+
+    fn encode_into(&self, out: &mut Vec<u8>, version: Version) {
+        match (self, version) {
+            // line breaks would end a simple string early
+            (Frame::Simple(value), _) => out
+                .extend_from_slice(format!("+{}\r\n", value.replace(['\r', '\n'], " ")).as_bytes()),
+            (Frame::Error(message), _) => out.extend_from_slice(
+                format!("-{}\r\n", message.replace(['\r', '\n'], " ")).as_bytes(),
+            ),
+            (Frame::Integer(value), _) => {
+                out.extend_from_slice(format!(":{}\r\n", value).as_bytes())
+            }
+            (Frame::Bulk(bytes), _) => {
+                out.extend_from_slice(format!("${}\r\n", bytes.len()).as_bytes());
+                out.extend_from_slice(bytes);
+                out.extend_from_slice(b"\r\n");
+            }
+            (Frame::Null, Version::Resp2) => out.extend_from_slice(b"$-1\r\n"),
+            (Frame::Null, Version::Resp3) => out.extend_from_slice(b"_\r\n"),
+            (Frame::Array(items), _) => {
+                out.extend_from_slice(format!("*{}\r\n", items.len()).as_bytes());
+                for item in items {
+                    item.encode_into(out, version);
+                }
+            }
+            (Frame::Map(entries), Version::Resp2) => {
+                out.extend_from_slice(format!("*{}\r\n", entries.len() * 2).as_bytes());
+                for (key, value) in entries {
+                    key.encode_into(out, version);
+                    value.encode_into(out, version);
+                }
+            }
+            (Frame::Map(entries), Version::Resp3) => {
+                out.extend_from_slice(format!("%{}\r\n", entries.len()).as_bytes());
+                for (key, value) in entries {
+                    key.encode_into(out, version);
+                    value.encode_into(out, version);
+                }
+            }
+            (Frame::Double(value), Version::Resp2) => {
+                Frame::Bulk(format_double(*value).into_bytes()).encode_into(out, version)
+            }
+            (Frame::Double(value), Version::Resp3) => {
+                out.extend_from_slice(format!(",{}\r\n", format_double(*value)).as_bytes())
+            }
+            (Frame::Boolean(value), Version::Resp2) => {
+                Frame::Integer(*value as i64).encode_into(out, version)
+            }
+            (Frame::Boolean(value), Version::Resp3) => {
+                out.extend_from_slice(if *value { b"#t\r\n" } else { b"#f\r\n" })
+            }
+        }
+    }
+}
+
+fn format_double(value: f64) -> String {
+    match value {
+        f64::INFINITY => "inf".into(),
+        f64::NEG_INFINITY => "-inf".into(),
+        value => value.to_string(),
+    }
+}
+
+impl From<ValueType> for Frame {
+    fn from(value: ValueType) -> Self {
+        match value {
+            ValueType::None => Frame::Null,
+            ValueType::Int(value) => Frame::Integer(value.into()),
+            ValueType::Int64(value) => Frame::Integer(value),
+            ValueType::UInt(value) => Frame::Integer(value.into()),
+            ValueType::UInt64(value) => match i64::try_from(value) {
+                Ok(value) => Frame::Integer(value),
+                Err(_) => Frame::Bulk(value.to_string().into_bytes()),
+            },
+            ValueType::Float(value) => Frame::Double(value.0),
+            ValueType::Bool(value) => Frame::Boolean(value),
+            ValueType::String(value) => Frame::Bulk(value.into_bytes()),
+            ValueType::Bytes(value) => Frame::Bulk(value),
+            ValueType::List(values) => Frame::Array(values.into_iter().map(Frame::from).collect()),
+            ValueType::Map(entries) => Frame::Map(
+                entries
+                    .into_iter()
+                    .map(|(field, value)| (Frame::Bulk(field.into_bytes()), Frame::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<ServerResponse> for Frame {
+    fn from(response: ServerResponse) -> Self {
+        let score =
+            |score: Option<Score>| score.map_or(Frame::Null, |score| Frame::Double(score.get()));
+        match response {
+            ServerResponse::Error { err } => Frame::Error(format!("ERR {}", err)),
+            ServerResponse::Ok => Frame::Simple("OK".into()),
+            ServerResponse::Single { value } => Frame::from(value),
+            ServerResponse::Bulk { values } => {
+                Frame::Array(values.into_iter().map(Frame::from).collect())
+            }
+            ServerResponse::OptInt { value } => {
+                value.map_or(Frame::Null, |value| Frame::Integer(value.into()))
+            }
+            ServerResponse::IntList { values } => Frame::Array(
+                values
+                    .into_iter()
+                    .map(|value| Frame::Integer(value.into()))
+                    .collect(),
+            ),
+            ServerResponse::OptScore { value } => score(value),
+            ServerResponse::Scores { values } => {
+                Frame::Array(values.into_iter().map(score).collect())
+            }
+            ServerResponse::ScoredBulk { values } => Frame::Array(
+                values
+                    .into_iter()
+                    .flat_map(|(member, score)| {
+                        [Frame::Bulk(member.into_bytes()), Frame::Double(score.get())]
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+fn hello_reply(version: Version) -> Frame {
+    let bulk = |value: &str| Frame::Bulk(value.as_bytes().to_vec());
+    Frame::Map(vec![
+        (bulk("server"), bulk("inferno")),
+        (bulk("version"), bulk(env!("CARGO_PKG_VERSION"))),
+        (
+            bulk("proto"),
+            Frame::Integer(match version {
+                Version::Resp2 => 2,
+                Version::Resp3 => 3,
+            }),
+        ),
+        (bulk("mode"), bulk("standalone")),
+        (bulk("role"), bulk("master")),
+        (bulk("modules"), Frame::Array(vec![])),
+    ])
+}
+
+enum Action {
+    Dispatch(Vec<ClientCommand>, Reply),
+    Batch(Batch),
+    Ttl(Key),
+    Reply(Frame),
+    Hello(Version),
+    Quit,
+}
+
+/// How the responses of the dispatched commands become one redis reply.
+enum Reply {
+    /// The response of the single command as is.
+    AsIs,
+    /// `OK` when the command reported a change, null otherwise.
+    OkOrNil,
+    /// First element of a bulk response, null when empty.
+    First,
+}
+
+impl Reply {
+    fn build(self, responses: Vec<Result<ServerResponse>>) -> Frame {
+        let responses = match responses.into_iter().collect::<Result<Vec<_>>>() {
+            Ok(responses) => responses,
+            Err(err) => return Frame::from(ServerResponse::Error { err }),
+        };
+        let mut responses = responses.into_iter();
+        match self {
+            Reply::AsIs => responses.next().map_or(Frame::Null, Frame::from),
+            Reply::OkOrNil => match responses.next() {
+                Some(ServerResponse::Single {
+                    value: ValueType::Int(1),
+                }) => Frame::Simple("OK".into()),
+                _ => Frame::Null,
+            },
+            Reply::First => match responses.next() {
+                Some(ServerResponse::Bulk { values }) => {
+                    values.into_iter().next().map_or(Frame::Null, Frame::from)
+                }
+                response => response.map_or(Frame::Null, Frame::from),
+            },
+        }
+    }
+}
+
+/// Commands taking several items which redis applies as one, there is no single native command
+/// for them so they run on the state directly and reply with a count.
+enum Batch {
+    Del(Vec<Key>),
+    HSet(Key, Vec<(String, ValueType)>),
+    ZAdd(Key, Vec<(Score, String)>),
+    ZRem(Key, Vec<String>),
+    /// Values pushed to the front when set, replying with the list length.
+    Push(Key, Vec<ValueType>, bool),
+}
+
+impl Batch {
+    fn apply(self, state: &State) -> Frame {
+        let count = match self {
+            Batch::Del(keys) => Ok(state.del_all(&keys)),
+            Batch::HSet(key, fields) => state.hset_all(key, fields),
+            Batch::ZAdd(key, members) => state.zadd_all(key, members),
+            Batch::ZRem(key, members) => state.zrem_all(key, &members),
+            Batch::Push(key, values, front) => state.push_all(key, values, front),
+        };
+        match count {
+            Ok(count) => Frame::Integer(count as i64),
+            Err(err) => Frame::from(ServerResponse::Error { err }),
+        }
+    }
+}
+
+/// Remaining arguments of a request, the command name is kept for error messages.
+struct Args {
+    name: String,
+    args: std::vec::IntoIter<Vec<u8>>,
+}
+
+impl Args {
+    fn remaining(&self) -> usize {
+        self.args.len()
+    }
+
+    fn arity(&self) -> Frame {
+        Frame::Error(format!(
+            "ERR wrong number of arguments for '{}' command",
+            self.name
+        ))
+    }
+
+    fn next(&mut self) -> std::result::Result<Vec<u8>, Frame> {
+        self.args.next().ok_or_else(|| self.arity())
+    }
+
+    fn end(&self) -> std::result::Result<(), Frame> {
+        if self.remaining() == 0 {
+            Ok(())
+        } else {
+            Err(Frame::Error("ERR syntax error".into()))
+        }
+    }
+
+    fn key(&mut self) -> std::result::Result<Key, Frame> {
+        self.next().map(Key::from)
+    }
+
+    fn string(&mut self) -> std::result::Result<String, Frame> {
+        String::from_utf8(self.next()?)
+            .map_err(|_| Frame::Error("ERR fields and members must be valid UTF-8".into()))
+    }
+
+    // redis values are plain strings, bytes only when they are not valid UTF-8
+    fn value(&mut self) -> std::result::Result<ValueType, Frame> {
+        Ok(match String::from_utf8(self.next()?) {
+            Ok(value) => ValueType::String(value),
+            Err(err) => ValueType::Bytes(err.into_bytes()),
+        })
+    }
+
+    fn number<T: FromStr>(&mut self) -> std::result::Result<T, Frame> {
+        let arg = self.next()?;
+        std::str::from_utf8(&arg)
+            .ok()
+            .and_then(|arg| arg.parse().ok())
+            .ok_or_else(|| Frame::Error("ERR value is not an integer or out of range".into()))
+    }
+
+    fn score(&mut self) -> std::result::Result<Score, Frame> {
+        let arg = self.next()?;
+        let score = match arg.to_ascii_lowercase().as_slice() {
+            b"+inf" | b"inf" => Some(Score::MAX),
+            b"-inf" => Some(Score::MIN),
+            arg => std::str::from_utf8(arg)
+                .ok()
+                .and_then(|arg| arg.parse().ok())
+                .and_then(Score::new),
+        };
+        score.ok_or_else(|| Frame::Error("ERR value is not a valid float".into()))
+    }
+
+    // only `-1` is understood as a negative index, meaning the end of the list
+    fn list_index(&mut self) -> std::result::Result<u32, Frame> {
+        match self.number::<i64>()? {
+            -1 => Ok(u32::MAX),
+            index => u32::try_from(index).map_err(|_| {
+                Frame::Error("ERR negative indexes other than -1 are not supported".into())
+            }),
+        }
+    }
+
+    fn each<T>(
+        &mut self,
+        mut parse: impl FnMut(&mut Self) -> std::result::Result<T, Frame>,
+    ) -> std::result::Result<Vec<T>, Frame> {
+        let mut items = Vec::new();
+        while self.remaining() > 0 {
+            items.push(parse(self)?);
+        }
+        if items.is_empty() {
+            return Err(self.arity());
+        }
+        Ok(items)
+    }
+}
+
+fn translate(args: Vec<Vec<u8>>) -> std::result::Result<Action, Frame> {
+    let mut args = args.into_iter();
+    let name = String::from_utf8_lossy(&args.next().unwrap_or_default()).to_ascii_lowercase();
+    let mut args = Args { name, args };
+
+    let one = |command| Action::Dispatch(vec![command], Reply::AsIs);
+    let action = match args.name.as_str() {
+        "ping" => match args.remaining() {
+            0 => Action::Reply(Frame::Simple("PONG".into())),
+            _ => Action::Reply(Frame::Bulk(args.next()?)),
+        },
+        "echo" => Action::Reply(Frame::Bulk(args.next()?)),
+        "hello" => {
+            let version = match args.remaining() {
+                0 => Version::Resp2,
+                _ => match args.number::<u8>() {
+                    Ok(2) => Version::Resp2,
+                    Ok(3) => Version::Resp3,
+                    _ => return Err(Frame::Error("NOPROTO unsupported protocol version".into())),
+                },
+            };
+            // authentication and client names are accepted but not used
+            return Ok(Action::Hello(version));
+        }
+        // client libraries probe the command table on connect
+        "command" => return Ok(Action::Reply(Frame::Array(vec![]))),
+        "select" => match args.number::<u32>()? {
+            0 => Action::Reply(Frame::Simple("OK".into())),
+            _ => Action::Reply(Frame::Error("ERR DB index is out of range".into())),
+        },
+        "quit" => return Ok(Action::Quit),
+
+        //// Value Commands ////
+        "get" => {
+            let key = args.key()?;
+            one(ClientCommand::Get { key, path: vec![] })
+        }
+        "set" => {
+            let key = args.key()?;
+            let value = args.value()?;
+            let (mut expire, mut nx) = (None, false);
+            while args.remaining() > 0 {
+                match args.next()?.to_ascii_lowercase().as_slice() {
+                    b"ex" => expire = Some(args.number()?),
+                    b"nx" => nx = true,
+                    _ => return Err(Frame::Error("ERR syntax error".into())),
+                }
+            }
+            match (expire, nx) {
+                (None, false) => one(ClientCommand::Set {
+                    key,
+                    path: vec![],
+                    value,
+                }),
+                (Some(expire), false) => one(ClientCommand::SetEx { key, value, expire }),
+                (None, true) => {
+                    Action::Dispatch(vec![ClientCommand::SetNx { key, value }], Reply::OkOrNil)
+                }
+                (Some(_), true) => {
+                    return Err(Frame::Error(
+                        "ERR SET with both EX and NX is not supported".into(),
+                    ))
+                }
+            }
+        }
+        "setnx" => {
+            let key = args.key()?;
+            let value = args.value()?;
+            one(ClientCommand::SetNx { key, value })
+        }
+        "setex" => {
+            let key = args.key()?;
+            let expire = args.number()?;
+            let value = args.value()?;
+            one(ClientCommand::SetEx { key, value, expire })
+        }
+        "getset" => {
+            let key = args.key()?;
+            let value = args.value()?;
+            one(ClientCommand::GetSet { key, value })
+        }
+        "getdel" => {
+            let key = args.key()?;
+            Action::Dispatch(
+                vec![ClientCommand::GetDel { keys: vec![key] }],
+                Reply::First,
+            )
+        }
+        "mget" => one(ClientCommand::MGet {
+            keys: args.each(Args::key)?,
+        }),
+        "mset" => {
+            if !args.remaining().is_multiple_of(2) {
+                return Err(args.arity());
+            }
+            let pairs = args.each(|args| Ok((args.key()?, args.value()?)))?;
+            let (keys, values) = pairs.into_iter().unzip();
+            one(ClientCommand::MSet { keys, values })
+        }
+        "del" => Action::Batch(Batch::Del(args.each(Args::key)?)),
+        "incr" => one(ClientCommand::Incr { key: args.key()? }),
+        "decr" => one(ClientCommand::Decr { key: args.key()? }),
+        "incrby" => {
+            let key = args.key()?;
+            one(ClientCommand::IncrBy {
+                key,
+                by: args.number()?,
+            })
+        }
+        "decrby" => {
+            let key = args.key()?;
+            one(ClientCommand::DecrBy {
+                key,
+                by: args.number()?,
+            })
+        }
+        "expire" => {
+            let key = args.key()?;
+            one(ClientCommand::Expire {
+                key,
+                expire: args.number()?,
+            })
+        }
+        "persist" => one(ClientCommand::Persist { key: args.key()? }),
+        "ttl" => Action::Ttl(args.key()?),
+
+        //// Hash Commands ////
+        "hset" => {
+            let key = args.key()?;
+            if !args.remaining().is_multiple_of(2) {
+                return Err(args.arity());
+            }
+            let fields = args.each(|args| Ok((args.string()?, args.value()?)))?;
+            Action::Batch(Batch::HSet(key, fields))
+        }
+        "hget" => {
+            let key = args.key()?;
+            one(ClientCommand::HGet {
+                key,
+                field: args.string()?,
+            })
+        }
+        "hdel" => {
+            let key = args.key()?;
+            one(ClientCommand::HDel {
+                key,
+                fields: args.each(Args::string)?,
+            })
+        }
+        "hexists" => {
+            let key = args.key()?;
+            one(ClientCommand::HExists {
+                key,
+                field: args.string()?,
+            })
+        }
+        "hincrby" => {
+            let key = args.key()?;
+            let field = args.string()?;
+            one(ClientCommand::HIncrBy {
+                key,
+                field,
+                by: args.number()?,
+            })
+        }
+        "hgetall" => one(ClientCommand::HGetAll { key: args.key()? }),
+        "hkeys" => one(ClientCommand::HKeys { key: args.key()? }),
+        "hvals" => one(ClientCommand::HValues { key: args.key()? }),
+        "hlen" => one(ClientCommand::HLen { key: args.key()? }),
+
+        //// List Commands ////
+        "lpush" | "rpush" => {
+            let front = args.name == "lpush";
+            let key = args.key()?;
+            Action::Batch(Batch::Push(key, args.each(Args::value)?, front))
+        }
+        "lpop" | "rpop" => {
+            let left = args.name == "lpop";
+            let key = args.key()?;
+            let (count, reply) = match args.remaining() {
+                0 => (1, Reply::First),
+                _ => (args.number()?, Reply::AsIs),
+            };
+            let command = if left {
+                ClientCommand::LLPop { key, count }
+            } else {
+                ClientCommand::LRPop { key, count }
+            };
+            Action::Dispatch(vec![command], reply)
+        }
+        "lrange" => {
+            let key = args.key()?;
+            let start = args.list_index()?;
+            let end = args.list_index()?;
+            one(ClientCommand::LRange { key, start, end })
+        }
+
+        //// Set Commands ////
+        "sadd" => {
+            let key = args.key()?;
+            one(ClientCommand::SAdd {
+                key,
+                members: args.each(Args::string)?,
+            })
+        }
+        "srem" => {
+            let key = args.key()?;
+            one(ClientCommand::SRem {
+                key,
+                members: args.each(Args::string)?,
+            })
+        }
+        "sismember" => {
+            let key = args.key()?;
+            one(ClientCommand::SMember {
+                key,
+                member: args.string()?,
+            })
+        }
+        "smembers" => one(ClientCommand::SMembers { key: args.key()? }),
+        "scard" => one(ClientCommand::SCard { key: args.key()? }),
+
+        //// Sorted Set Commands ////
+        "zadd" => {
+            let key = args.key()?;
+            if !args.remaining().is_multiple_of(2) {
+                return Err(args.arity());
+            }
+            let members = args.each(|args| Ok((args.score()?, args.string()?)))?;
+            Action::Batch(Batch::ZAdd(key, members))
+        }
+        "zincrby" => {
+            let key = args.key()?;
+            let by = args.score()?;
+            one(ClientCommand::ZIncrBy {
+                key,
+                by,
+                member: args.string()?,
+            })
+        }
+        "zscore" => {
+            let key = args.key()?;
+            one(ClientCommand::ZScore {
+                key,
+                member: args.string()?,
+            })
+        }
+        "zrem" => {
+            let key = args.key()?;
+            Action::Batch(Batch::ZRem(key, args.each(Args::string)?))
+        }
+        "zrange" => {
+            let key = args.key()?;
+            let start = args.number()?;
+            let stop = args.number()?;
+            one(ClientCommand::ZRange { key, start, stop })
+        }
+        "zcard" => one(ClientCommand::ZCard { key: args.key()? }),
+
+        _ => return Err(Frame::Error(format!("ERR unknown command '{}'", args.name))),
+    };
+    args.end()?;
+    Ok(action)
+}
@@ -1,367 +1,1615 @@
-use bztree::BzTree;
-use dashmap::{DashMap, DashSet};
-use errors::{Result, StateError};
+use dashmap::mapref::entry::Entry;
+use dashmap::mapref::one::{Ref, RefMut};
+use dashmap::DashMap;
+use errors::{PacketsError, Result, StateError};
 use packets::{ClientCommandExecutor, ServerResponse};
-use std::ops::Neg;
-use std::sync::Arc;
-
-use crate::data::list::ArcSwapLinkedList;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Instant;
+
+use crate::container::Container;
+use crate::data::expiring::Expiring;
+use crate::data::list::{ArcSwapLinkedList, LikeLinkedList};
+use crate::data::sorted_set::SortedSet;
+use crate::expiry::{self, Clock, SystemClock};
+use packets::frame::MAX_NESTING_DEPTH;
+use packets::instruction::Instruction;
+use packets::key::Key;
+use packets::score::Score;
 use packets::value::ValueType;
+use rand::seq::IteratorRandom;
 
-#[derive(Default, Clone)]
+#[derive(Clone)]
 pub struct State {
-    map: Arc<DashMap<String, CompositeValue>>,
+    map: Arc<DashMap<Key, CompositeValue>>,
+    // deadlines are only ever touched while holding the key's entry in `map`
+    expirations: Arc<DashMap<Key, Instant>>,
+    // deadlines of collection members, soonest first per key, so reclaiming only ever visits
+    // members which were given one
+    member_expirations: Arc<DashMap<Key, BTreeMap<Instant, Vec<Member>>>>,
+    clock: Arc<dyn Clock>,
+    // single key commands hold this shared, commands spanning several keys hold it exclusively
+    keyspace: Arc<RwLock<()>>,
 }
 
-impl ClientCommandExecutor for &State {
-    async fn expire(self, key: String, expire: u32) -> Result<ServerResponse> {
-        unimplemented!()
-    }
+// a collection member which was given a deadline, list items have no name to find them by
+enum Member {
+    Field(String),
+    Value(ValueType),
+    Item,
+}
 
-    async fn persist(self, key: String) -> Result<ServerResponse> {
-        unimplemented!()
+impl Default for State {
+    fn default() -> Self {
+        Self::with_clock(SystemClock)
     }
+}
 
-    async fn ttl(self, key: String) -> Result<ServerResponse> {
-        unimplemented!()
+impl State {
+    pub fn with_clock(clock: impl Clock + 'static) -> Self {
+        Self {
+            map: Default::default(),
+            expirations: Default::default(),
+            member_expirations: Default::default(),
+            clock: Arc::new(clock),
+            keyspace: Default::default(),
+        }
     }
 
-    async fn del(self, keys: Vec<String>) -> Result<ServerResponse> {
-        for key in keys {
-            self.map.remove(&key);
-        }
-        Ok(ServerResponse::Ok)
+    pub fn len(&self) -> usize {
+        self.map.len()
     }
 
-    async fn decr(self, key: String) -> Result<ServerResponse> {
-        self.decr_by(key, 1).await
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
     }
 
-    async fn decr_by(self, key: String, by: u32) -> Result<ServerResponse> {
-        if by > i32::MAX as u32 {
-            // todo: fix error name
-            Err(StateError::BadState)?;
-        }
-        let by = by as i32;
-        let value = self.map.get_mut(&key);
-        let Some(mut value) = value else {
-            self.map
-                .insert(key, CompositeValue::Value(ValueType::Int(by.neg())));
-            return Ok(ServerResponse::Single {
-                value: ValueType::Int(1),
-            });
-        };
+    /// Whether `key` currently holds a value.
+    pub fn contains_key(&self, key: &Key) -> bool {
+        let _guard = self.shared();
+        self.live(key).is_some()
+    }
 
-        match value.value().value() {
-            Ok(ValueType::Int(v)) => {
-                if v < i32::MIN + by {
-                    // todo: fix error name
-                    Err(StateError::BadState)?;
-                }
+    /// Removes every key in `keys` at once, returning how many existed.
+    pub(crate) fn del_all(&self, keys: &[Key]) -> usize {
+        let _guard = self.exclusive();
+        keys.iter().filter(|key| self.remove(key).is_some()).count()
+    }
 
-                *value.value_mut() = CompositeValue::Value(ValueType::Int(v - by));
-                Ok(ServerResponse::Single {
-                    value: ValueType::Int(v - by),
-                })
-            }
-            _ => Err(StateError::BadKeyType)?,
-        }
+    /// Sets every field of the hash at `key` at once, returning how many were created.
+    pub(crate) fn hset_all(&self, key: Key, fields: Vec<(String, ValueType)>) -> Result<usize> {
+        let fields = fields
+            .into_iter()
+            .map(|(field, value)| Ok((field, CompositeValue::from_value_at(value, 1)?)))
+            .collect::<Result<Vec<_>>>()?;
+        let _guard = self.shared();
+        let now = self.clock.now();
+        let entry = self.hash_or_default(key);
+        let map = entry.value().map()?;
+        Ok(fields
+            .into_iter()
+            .map(|(field, value)| map.ct_insert(&field, value, now))
+            .filter(|created| *created)
+            .count())
     }
 
-    async fn incr(self, key: String) -> Result<ServerResponse> {
-        self.incr_by(key, 1).await
+    /// Adds every member to the sorted set at `key` at once, returning how many were created.
+    pub(crate) fn zadd_all(&self, key: Key, members: Vec<(Score, String)>) -> Result<usize> {
+        let _guard = self.shared();
+        let now = self.clock.now();
+        let entry = self.ord_set_or_default(key);
+        let ord_set = entry.value().ord_set()?;
+        Ok(members
+            .into_iter()
+            .map(|(score, member)| ord_set.insert(member, score, None, now))
+            .filter(|created| *created)
+            .count())
     }
 
-    async fn incr_by(self, key: String, by: u32) -> Result<ServerResponse> {
-        if by > i32::MAX as u32 {
-            // todo: fix error name
-            Err(StateError::BadState)?;
+    /// Removes every member from the sorted set at `key` at once, returning how many existed.
+    pub(crate) fn zrem_all(&self, key: Key, members: &[String]) -> Result<usize> {
+        let _guard = self.shared();
+        let now = self.clock.now();
+        let removed = {
+            let Some(value) = self.live_mut(&key) else {
+                return Ok(0);
+            };
+            let ord_set = value.value().ord_set()?;
+            members
+                .iter()
+                .filter(|member| ord_set.remove(member, now).is_some())
+                .count()
+        };
+        self.remove_if_empty(&key);
+        Ok(removed)
+    }
+
+    /// Pushes `values` in order onto the front or back of the list at `key` at once, returning the
+    /// length of the list after the last one.
+    pub(crate) fn push_all(&self, key: Key, values: Vec<ValueType>, front: bool) -> Result<usize> {
+        let values = values
+            .into_iter()
+            .map(|value| CompositeValue::from_value_at(value, 1))
+            .collect::<Result<Vec<_>>>()?;
+        let _guard = self.shared();
+        let now = self.clock.now();
+        let entry = self.list_or_default(key);
+        let list = entry.value().list()?;
+        for value in values {
+            let item = Expiring::new(value, None);
+            if front {
+                list.push_front(item);
+            } else {
+                list.push_back(item);
+            }
         }
-        let by = by as i32;
-        let value = self.map.get_mut(&key);
-        let Some(mut value) = value else {
-            self.map
-                .insert(key, CompositeValue::Value(ValueType::Int(by)));
-            return Ok(ServerResponse::Single {
-                value: ValueType::Int(1),
-            });
+        Ok(list.iter().filter(|item| item.is_live(now)).count())
+    }
+
+    /// Removes every key whose deadline has passed along with every collection member whose
+    /// deadline has passed, returning how many keys were removed.
+    pub fn sweep(&self) -> usize {
+        let now = self.clock.now();
+        let expired = self
+            .expirations
+            .iter()
+            .filter(|deadline| *deadline.value() <= now)
+            .map(|deadline| deadline.key().clone())
+            .collect::<Vec<_>>();
+
+        let swept = {
+            let _guard = self.shared();
+            expired.iter().filter(|key| self.purge(key)).count()
         };
 
-        match value.value().value() {
-            Ok(ValueType::Int(v)) => {
-                if v > i32::MAX - by {
-                    // todo: fix error name
-                    Err(StateError::BadState)?;
-                }
-
-                *value.value_mut() = CompositeValue::Value(ValueType::Int(v + by));
-                Ok(ServerResponse::Single {
-                    value: ValueType::Int(v + by),
-                })
-            }
-            _ => Err(StateError::BadKeyType)?,
+        let due = self
+            .member_expirations
+            .iter()
+            .filter(|deadlines| {
+                deadlines
+                    .keys()
+                    .next()
+                    .is_some_and(|deadline| *deadline <= now)
+            })
+            .map(|deadlines| deadlines.key().clone())
+            .collect::<Vec<_>>();
+        if due.is_empty() {
+            return swept;
         }
-    }
 
-    async fn get(self, key: String) -> Result<ServerResponse> {
-        unimplemented!()
+        // lists are rebuilt while reclaiming, nothing else may touch them meanwhile
+        let _guard = self.exclusive();
+        swept + due.iter().filter(|key| self.reclaim(key, true)).count()
     }
 
-    async fn get_del(self, keys: Vec<String>) -> Result<ServerResponse> {
-        let mut response = Vec::new();
-        for key in keys {
-            let opt_val = self.map.remove(&key);
-            let Some(value) = opt_val.and_then(|(_, value)| value.value().ok()) else {
-                continue;
-            };
-            response.push(value);
-        }
+    fn shared(&self) -> RwLockReadGuard<'_, ()> {
+        self.keyspace.read().unwrap_or_else(PoisonError::into_inner)
+    }
 
-        Ok(ServerResponse::Bulk { values: response })
+    fn exclusive(&self) -> RwLockWriteGuard<'_, ()> {
+        self.keyspace
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
     }
 
-    async fn get_ex(self, key: String, expire: u32) -> Result<ServerResponse> {
-        unimplemented!()
+    // drops `key` once its deadline has passed or nothing live is left in it
+    fn purge(&self, key: &Key) -> bool {
+        let now = self.clock.now();
+        let expired = self
+            .map
+            .remove_if(key, |key, _| {
+                let expired = self
+                    .expirations
+                    .remove_if(key, |_, deadline| *deadline <= now)
+                    .is_some();
+                if expired {
+                    self.member_expirations.remove(key);
+                }
+                expired
+            })
+            .is_some();
+        expired || (self.member_expirations.contains_key(key) && self.reclaim(key, false))
+    }
+
+    // drops the members of the collection at `key` whose deadline has passed, then the key itself
+    // once nothing live is left; list items can only be dropped by rebuilding the whole list, so
+    // that waits for a caller holding the keyspace exclusively
+    fn reclaim(&self, key: &Key, rebuild: bool) -> bool {
+        let now = self.clock.now();
+        self.map
+            .remove_if(key, |key, value| {
+                let due = match self.member_expirations.get_mut(key) {
+                    Some(_) if !rebuild && matches!(value, CompositeValue::List(_)) => Vec::new(),
+                    Some(mut deadlines) => {
+                        let mut due = Vec::new();
+                        while let Some(entry) = deadlines.first_entry() {
+                            if *entry.key() > now {
+                                break;
+                            }
+                            due.extend(entry.remove());
+                        }
+                        due
+                    }
+                    None => return false,
+                };
+                value.reclaim(&due, now);
+                self.member_expirations
+                    .remove_if(key, |_, deadlines| deadlines.is_empty());
+                let empty = !value.has_live(now);
+                if empty {
+                    self.expirations.remove(key);
+                    self.member_expirations.remove(key);
+                }
+                empty
+            })
+            .is_some()
+    }
+
+    // records that `member` of the collection at `key` expires at `deadline`
+    fn track_member(&self, key: Key, member: Member, deadline: Instant) {
+        self.member_expirations
+            .entry(key)
+            .or_default()
+            .entry(deadline)
+            .or_default()
+            .push(member);
+    }
+
+    // removes the collection at `key` if its last member was just taken out
+    fn remove_if_empty(&self, key: &Key) {
+        self.map.remove_if(key, |key, value| {
+            let empty = value.is_empty();
+            if empty {
+                self.expirations.remove(key);
+                self.member_expirations.remove(key);
+            }
+            empty
+        });
     }
 
-    async fn get_set(self, key: String, value: ValueType) -> Result<ServerResponse> {
-        unimplemented!()
+    fn live(&self, key: &Key) -> Option<Ref<'_, Key, CompositeValue>> {
+        self.purge(key);
+        self.map.get(key)
     }
 
-    async fn mget(self, keys: Vec<String>) -> Result<ServerResponse> {
-        unimplemented!()
+    fn live_mut(&self, key: &Key) -> Option<RefMut<'_, Key, CompositeValue>> {
+        self.purge(key);
+        self.map.get_mut(key)
     }
 
-    async fn set(self, key: String, value: ValueType) -> Result<ServerResponse> {
-        unimplemented!()
+    fn live_entry(&self, key: Key) -> Entry<'_, Key, CompositeValue> {
+        self.purge(&key);
+        self.map.entry(key)
     }
 
-    async fn set_ex(self, key: String, value: ValueType, expire: u32) -> Result<ServerResponse> {
-        unimplemented!()
+    fn remove(&self, key: &Key) -> Option<CompositeValue> {
+        match self.live_entry(key.clone()) {
+            Entry::Occupied(entry) => {
+                self.expirations.remove(entry.key());
+                self.member_expirations.remove(entry.key());
+                Some(entry.remove())
+            }
+            Entry::Vacant(_) => None,
+        }
     }
 
-    async fn set_nx(self, key: String, value: ValueType) -> Result<ServerResponse> {
-        unimplemented!()
+    // overwrites the key with a fresh value, dropping any deadline it had
+    fn replace(&self, key: Key, value: CompositeValue) {
+        let entry = self.map.entry(key);
+        self.expirations.remove(entry.key());
+        self.member_expirations.remove(entry.key());
+        entry.insert(value);
     }
 
-    async fn mset(self, keys: Vec<String>, values: Vec<ValueType>) -> Result<ServerResponse> {
-        unimplemented!()
+    // the hash at `key`, created empty when missing; the key stays locked while this is held
+    fn hash_or_default(&self, key: Key) -> RefMut<'_, Key, CompositeValue> {
+        self.live_entry(key)
+            .or_insert_with(|| CompositeValue::Map(Default::default()))
     }
 
-    async fn mset_nx(self, keys: Vec<String>, values: Vec<ValueType>) -> Result<ServerResponse> {
-        unimplemented!()
+    fn with_hash<T>(
+        &self,
+        key: &Key,
+        read: impl FnOnce(&Arc<DashMap<String, Expiring<CompositeValue>>>, Instant) -> T,
+    ) -> Result<Option<T>> {
+        let Some(value) = self.live(key) else {
+            return Ok(None);
+        };
+        let map = value.value().map()?;
+        Ok(Some(read(&map, self.clock.now())))
     }
 
-    async fn hexpire(self, key: String, field: String, expire: u32) -> Result<ServerResponse> {
-        unimplemented!()
+    // the sorted set at `key`, created empty when missing; the key stays locked while this is held
+    fn ord_set_or_default(&self, key: Key) -> RefMut<'_, Key, CompositeValue> {
+        self.live_entry(key)
+            .or_insert_with(|| CompositeValue::OrdSet(Default::default()))
     }
 
-    async fn hdel(self, key: String, fields: Vec<String>) -> Result<ServerResponse> {
-        unimplemented!()
+    fn with_ord_set<T>(
+        &self,
+        key: &Key,
+        read: impl FnOnce(&SortedSet, Instant) -> T,
+    ) -> Result<Option<T>> {
+        let Some(value) = self.live(key) else {
+            return Ok(None);
+        };
+        let ord_set = value.value().ord_set()?;
+        Ok(Some(read(&ord_set, self.clock.now())))
     }
 
-    async fn hdel_get(self, key: String, fields: Vec<String>) -> Result<ServerResponse> {
-        unimplemented!()
+    fn ord_set_pop(&self, key: Key, count: u32, from_max: bool) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let now = self.clock.now();
+        let popped = {
+            let Some(value) = self.live_mut(&key) else {
+                return Ok(ServerResponse::ScoredBulk { values: vec![] });
+            };
+            let ord_set = value.value().ord_set()?;
+            if from_max {
+                ord_set.pop_max(count as usize, now)
+            } else {
+                ord_set.pop_min(count as usize, now)
+            }
+        };
+        self.remove_if_empty(&key);
+        Ok(ServerResponse::ScoredBulk { values: popped })
     }
 
-    async fn hpop_rand(self, key: String, count: u32) -> Result<ServerResponse> {
-        unimplemented!()
+    // the list at `key`, created empty when missing; the key stays locked while this is held
+    fn list_or_default(&self, key: Key) -> RefMut<'_, Key, CompositeValue> {
+        self.live_entry(key)
+            .or_insert_with(|| CompositeValue::List(Default::default()))
     }
 
-    async fn hexists(self, key: String, field: String) -> Result<ServerResponse> {
-        unimplemented!()
+    // creates a list holding only `value`, unless `key` already exists
+    fn list_nx(&self, key: Key, value: CompositeValue) -> bool {
+        match self.live_entry(key) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                let list = ArcSwapLinkedList::new_with(Expiring::new(value, None));
+                entry.insert(CompositeValue::List(Arc::new(list)));
+                true
+            }
+        }
     }
 
-    async fn hget(self, key: String, field: String) -> Result<ServerResponse> {
-        unimplemented!()
+    fn list_pop(&self, key: Key, count: u32, from_back: bool) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let now = self.clock.now();
+        let popped = {
+            let Some(value) = self.live_mut(&key) else {
+                return Ok(ServerResponse::Bulk { values: vec![] });
+            };
+            let list = value.value().list()?;
+            let pop = || {
+                if from_back {
+                    list.pop_back()
+                } else {
+                    list.pop_front()
+                }
+            };
+            // expired members are dropped on the way without counting towards `count`
+            std::iter::from_fn(pop)
+                .filter(|item| item.is_live(now))
+                .take(count as usize)
+                .map(|item| item.into_value().to_value(now))
+                .collect::<Result<_>>()?
+        };
+        self.remove_if_empty(&key);
+        Ok(ServerResponse::Bulk { values: popped })
     }
 
-    async fn hget_all(self, key: String) -> Result<ServerResponse> {
-        unimplemented!()
+    // the set at `key`, created empty when missing; the key stays locked while this is held
+    fn set_or_default(&self, key: Key) -> RefMut<'_, Key, CompositeValue> {
+        self.live_entry(key)
+            .or_insert_with(|| CompositeValue::Set(Default::default()))
     }
 
-    async fn hmget(self, key: String, fields: Vec<String>) -> Result<ServerResponse> {
-        unimplemented!()
+    fn with_set<T>(
+        &self,
+        key: &Key,
+        read: impl FnOnce(&Arc<DashMap<ValueType, Expiring<()>>>, Instant) -> T,
+    ) -> Result<Option<T>> {
+        let Some(value) = self.live(key) else {
+            return Ok(None);
+        };
+        let set = value.value().set()?;
+        Ok(Some(read(&set, self.clock.now())))
+    }
+
+    fn set_members(&self, key: &Key) -> Result<HashSet<ValueType>> {
+        Ok(self
+            .with_set(key, |set, now| {
+                set.iter()
+                    .filter(|member| member.is_live(now))
+                    .map(|member| member.key().clone())
+                    .collect()
+            })?
+            .unwrap_or_default())
+    }
+
+    // folds the live members of every set in `keys`, callers hold the exclusive lock so the sets
+    // are read at a single point in time
+    fn combine_sets(
+        &self,
+        keys: &[Key],
+        mut combine: impl FnMut(&mut HashSet<ValueType>, HashSet<ValueType>),
+    ) -> Result<HashSet<ValueType>> {
+        let Some((first, rest)) = keys.split_first() else {
+            return Ok(HashSet::new());
+        };
+        let mut members = self.set_members(first)?;
+        for key in rest {
+            combine(&mut members, self.set_members(key)?);
+        }
+        Ok(members)
     }
 
-    async fn hkeys(self, key: String) -> Result<ServerResponse> {
-        unimplemented!()
+    fn sunion_members(&self, keys: &[Key]) -> Result<HashSet<ValueType>> {
+        self.combine_sets(keys, |members, other| members.extend(other))
     }
 
-    async fn hvalues(self, key: String) -> Result<ServerResponse> {
-        unimplemented!()
+    fn sinter_members(&self, keys: &[Key]) -> Result<HashSet<ValueType>> {
+        self.combine_sets(keys, |members, other| {
+            members.retain(|member| other.contains(member))
+        })
     }
 
-    async fn hlen(self, key: String) -> Result<ServerResponse> {
-        unimplemented!()
+    fn sdiff_members(&self, keys: &[Key]) -> Result<HashSet<ValueType>> {
+        self.combine_sets(keys, |members, other| {
+            members.retain(|member| !other.contains(member))
+        })
     }
 
-    async fn hdecr(self, key: String, field: String) -> Result<ServerResponse> {
-        unimplemented!()
+    // overwrites `destination` with `members`, an empty result deletes it instead
+    fn store_set(&self, destination: Key, members: HashSet<ValueType>) -> ServerResponse {
+        let len = members.len();
+        if members.is_empty() {
+            self.remove(&destination);
+        } else {
+            let set = members
+                .into_iter()
+                .map(|member| (member, Expiring::new((), None)))
+                .collect();
+            self.replace(destination, CompositeValue::Set(Arc::new(set)));
+        }
+        ServerResponse::Single {
+            value: ValueType::Int(len as i32),
+        }
     }
 
-    async fn hdecr_by(self, key: String, field: String) -> Result<ServerResponse> {
-        unimplemented!()
+    fn hash_add(&self, key: Key, field: String, by: i64) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let now = self.clock.now();
+        // neither the hash nor the field is created unless the sum fits
+        let entry = self.live_entry(key);
+        let map = match &entry {
+            Entry::Occupied(entry) => entry.get().map()?,
+            Entry::Vacant(_) => Default::default(),
+        };
+        let updated = match map.entry(field) {
+            Entry::Occupied(mut field) if field.get().is_live(now) => {
+                let updated = checked_add(&field.get().value().value()?, by)?;
+                let deadline = field.get().deadline();
+                field.insert(Expiring::new(
+                    CompositeValue::Value(updated.clone()),
+                    deadline,
+                ));
+                updated
+            }
+            field => {
+                let updated = checked_add(&ValueType::Int(0), by)?;
+                field.insert(Expiring::new(CompositeValue::Value(updated.clone()), None));
+                updated
+            }
+        };
+        if let Entry::Vacant(entry) = entry {
+            entry.insert(CompositeValue::Map(map));
+        }
+        Ok(ServerResponse::Single { value: updated })
     }
 
-    async fn hincr(self, key: String, field: String) -> Result<ServerResponse> {
-        unimplemented!()
+    fn read_value(&self, key: &Key) -> Result<ValueType> {
+        match self.live(key) {
+            Some(value) => value.value().to_value(self.clock.now()),
+            None => Ok(ValueType::None),
+        }
     }
+}
 
-    async fn hincr_by(self, key: String, field: String, by: u32) -> Result<ServerResponse> {
-        unimplemented!()
+// adds `by` to an integer of any width, the result keeps the width of `value`
+fn checked_add(value: &ValueType, by: i64) -> Result<ValueType> {
+    let updated = match *value {
+        ValueType::Int(int) => (int as i64)
+            .checked_add(by)
+            .and_then(|int| int.try_into().ok())
+            .map(ValueType::Int),
+        ValueType::Int64(int) => int.checked_add(by).map(ValueType::Int64),
+        ValueType::UInt(int) => (int as i64)
+            .checked_add(by)
+            .and_then(|int| int.try_into().ok())
+            .map(ValueType::UInt),
+        ValueType::UInt64(int) => int.checked_add_signed(by).map(ValueType::UInt64),
+        _ => Err(StateError::BadKeyType)?,
+    };
+    Ok(updated.ok_or(StateError::Overflow)?)
+}
+
+// stored values nest no deeper than decoded ones, counted from the top of the key
+fn check_depth(depth: usize) -> Result<()> {
+    if depth >= MAX_NESTING_DEPTH {
+        Err(PacketsError::NestingTooDeep {
+            limit: MAX_NESTING_DEPTH,
+        })?;
     }
+    Ok(())
+}
 
-    async fn hset(self, key: String, field: String, value: ValueType) -> Result<ServerResponse> {
-        unimplemented!()
+impl ClientCommandExecutor for &State {
+    async fn expire(self, key: Key, expire: u32) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let Some(value) = self.live(&key) else {
+            return Ok(ServerResponse::Single {
+                value: ValueType::Int(0),
+            });
+        };
+        self.expirations
+            .insert(value.key().clone(), expiry::deadline(&*self.clock, expire));
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1),
+        })
     }
 
-    async fn hset_nx(self, key: String, field: String, value: ValueType) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn persist(self, key: Key) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let Some(value) = self.live(&key) else {
+            return Ok(ServerResponse::Single {
+                value: ValueType::Int(0),
+            });
+        };
+        let persisted = self.expirations.remove(value.key()).is_some();
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(persisted as i32),
+        })
     }
 
-    async fn hset_ex(
-        self,
-        key: String,
-        field: String,
-        value: ValueType,
-        expire: u32,
-    ) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn ttl(self, key: Key) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let Some(value) = self.live(&key) else {
+            return Ok(ServerResponse::OptInt { value: None });
+        };
+        let remaining = self.expirations.get(value.key()).map(|deadline| {
+            let remaining = deadline.saturating_duration_since(self.clock.now());
+            remaining.as_secs_f64().round() as u32
+        });
+        Ok(ServerResponse::OptInt { value: remaining })
+    }
+
+    async fn del(self, key: Key, path: Vec<Instruction>) -> Result<ServerResponse> {
+        let removed = if path.is_empty() {
+            let _guard = self.shared();
+            self.remove(&key).is_some()
+        } else {
+            // nested lists are edited by rebuilding them, which needs every other writer held off
+            let _guard = self.exclusive();
+            let removed = match self.live_mut(&key) {
+                Some(value) => value.value().del_path(&path, self.clock.now())?,
+                None => false,
+            };
+            self.remove_if_empty(&key);
+            removed
+        };
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(removed as i32),
+        })
     }
 
-    async fn hmset(self, key: String, fields: Vec<(String, ValueType)>) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn ping(self) -> Result<ServerResponse> {
+        Ok(ServerResponse::Ok)
     }
 
-    async fn hmset_nx(
-        self,
-        key: String,
-        fields: Vec<(String, ValueType)>,
-    ) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn decr(self, key: Key) -> Result<ServerResponse> {
+        self.decr_by(key, 1).await
     }
 
-    async fn zadd(self, key: String, score: u32, member: String) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn decr_by(self, key: Key, by: i64) -> Result<ServerResponse> {
+        self.incr_by(key, by.checked_neg().ok_or(StateError::Overflow)?)
+            .await
     }
 
-    async fn zadd_nx(self, key: String, score: u32, member: String) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn incr(self, key: Key) -> Result<ServerResponse> {
+        self.incr_by(key, 1).await
     }
 
-    async fn zincr_by(self, key: String, score: u32, member: String) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn incr_by(self, key: Key, by: i64) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let entry = self.live_entry(key);
+        let current = match &entry {
+            Entry::Occupied(entry) => entry.get().value()?,
+            Entry::Vacant(_) => ValueType::Int(0),
+        };
+        let updated = checked_add(&current, by)?;
+
+        entry.insert(CompositeValue::Value(updated.clone()));
+        Ok(ServerResponse::Single { value: updated })
     }
 
-    async fn zdecr_by(self, key: String, score: u32, member: String) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn get(self, key: Key, path: Vec<Instruction>) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let value = match self.live(&key) {
+            Some(value) => value.value().get_path(&path, self.clock.now())?,
+            None => ValueType::None,
+        };
+        Ok(ServerResponse::Single { value })
     }
 
-    async fn zscore(self, key: String, member: String) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn get_del(self, keys: Vec<Key>) -> Result<ServerResponse> {
+        let _guard = self.exclusive();
+        for key in &keys {
+            self.read_value(key)?;
+        }
+
+        let mut response = Vec::new();
+        for key in keys {
+            let Some(value) = self.remove(&key) else {
+                continue;
+            };
+            response.push(value.to_value(self.clock.now())?);
+        }
+
+        Ok(ServerResponse::Bulk { values: response })
     }
 
-    async fn zmscore(self, key: String, members: Vec<String>) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn get_ex(self, key: Key, expire: u32) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let Some(value) = self.live(&key) else {
+            return Ok(ServerResponse::Single {
+                value: ValueType::None,
+            });
+        };
+        let response = value.value().to_value(self.clock.now())?;
+        self.expirations
+            .insert(value.key().clone(), expiry::deadline(&*self.clock, expire));
+        Ok(ServerResponse::Single { value: response })
+    }
+
+    async fn get_set(self, key: Key, value: ValueType) -> Result<ServerResponse> {
+        let value = CompositeValue::from_value(value)?;
+        let _guard = self.shared();
+        let old = match self.live_entry(key) {
+            Entry::Occupied(mut entry) => {
+                let old = entry.get().to_value(self.clock.now())?;
+                self.expirations.remove(entry.key());
+                entry.insert(value);
+                old
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                ValueType::None
+            }
+        };
+        Ok(ServerResponse::Single { value: old })
     }
 
-    async fn zpop_min(self, key: String, count: u32) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn mget(self, keys: Vec<Key>) -> Result<ServerResponse> {
+        let _guard = self.exclusive();
+        let values = keys
+            .iter()
+            .map(|key| self.read_value(key))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(ServerResponse::Bulk { values })
     }
 
-    async fn zpop_max(self, key: String, count: u32) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn set(
+        self,
+        key: Key,
+        path: Vec<Instruction>,
+        value: ValueType,
+    ) -> Result<ServerResponse> {
+        // each step of the path is a collection above the value
+        if let Some(deepest) = path.len().checked_sub(1) {
+            check_depth(deepest)?;
+        }
+        let value = CompositeValue::from_value_at(value, path.len())?;
+        let Some(first) = path.first() else {
+            let _guard = self.shared();
+            self.replace(key, value);
+            return Ok(ServerResponse::Ok);
+        };
+        // nested lists are edited by rebuilding them, which needs every other writer held off
+        let _guard = self.exclusive();
+        let result = {
+            let entry = self
+                .live_entry(key.clone())
+                .or_insert_with(|| CompositeValue::container_for(first));
+            entry.value().set_path(&path, value, self.clock.now())
+        };
+        // a failed write into a fresh key leaves nothing behind
+        self.remove_if_empty(&key);
+        result.map(|_| ServerResponse::Ok)
+    }
+
+    async fn set_ex(self, key: Key, value: ValueType, expire: u32) -> Result<ServerResponse> {
+        let value = CompositeValue::from_value(value)?;
+        let _guard = self.shared();
+        let entry = self.map.entry(key);
+        self.expirations
+            .insert(entry.key().clone(), expiry::deadline(&*self.clock, expire));
+        entry.insert(value);
+        Ok(ServerResponse::Ok)
     }
 
-    async fn zrem(self, key: String, member: String) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn set_nx(self, key: Key, value: ValueType) -> Result<ServerResponse> {
+        let value = CompositeValue::from_value(value)?;
+        let _guard = self.shared();
+        let inserted = match self.live_entry(key) {
+            Entry::Occupied(_) => 0,
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                1
+            }
+        };
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(inserted),
+        })
     }
 
-    async fn zexpire(self, key: String, member: String, expire: u32) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn mset(self, keys: Vec<Key>, values: Vec<ValueType>) -> Result<ServerResponse> {
+        if keys.len() != values.len() {
+            Err(StateError::MismatchedArguments)?;
+        }
+        let values = values
+            .into_iter()
+            .map(CompositeValue::from_value)
+            .collect::<Result<Vec<_>>>()?;
+        let _guard = self.exclusive();
+        for (key, value) in keys.into_iter().zip(values) {
+            self.replace(key, value);
+        }
+        Ok(ServerResponse::Ok)
     }
 
-    async fn llpush(self, key: String, value: ValueType) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn mset_nx(self, keys: Vec<Key>, values: Vec<ValueType>) -> Result<ServerResponse> {
+        if keys.len() != values.len() {
+            Err(StateError::MismatchedArguments)?;
+        }
+        let values = values
+            .into_iter()
+            .map(CompositeValue::from_value)
+            .collect::<Result<Vec<_>>>()?;
+        let _guard = self.exclusive();
+        if keys.iter().any(|key| self.live(key).is_some()) {
+            return Ok(ServerResponse::Single {
+                value: ValueType::Int(0),
+            });
+        }
+        for (key, value) in keys.into_iter().zip(values) {
+            self.replace(key, value);
+        }
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1),
+        })
     }
 
-    async fn llpush_nx(self, key: String, value: ValueType) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn hexpire(self, key: Key, field: String, expire: u32) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let now = self.clock.now();
+        let Some(value) = self.live(&key) else {
+            return Ok(ServerResponse::Single {
+                value: ValueType::Int(0),
+            });
+        };
+        let map = value.value().map()?;
+        let Some(field) = map.get(&field).filter(|field| field.is_live(now)) else {
+            return Ok(ServerResponse::Single {
+                value: ValueType::Int(0),
+            });
+        };
+        let deadline = expiry::deadline(&*self.clock, expire);
+        field.set_deadline(Some(deadline));
+        self.track_member(key, Member::Field(field.key().clone()), deadline);
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1),
+        })
+    }
+
+    async fn hdel(self, key: Key, fields: Vec<String>) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let now = self.clock.now();
+        let removed = {
+            let Some(value) = self.live_mut(&key) else {
+                return Ok(ServerResponse::Single {
+                    value: ValueType::Int(0),
+                });
+            };
+            let map = value.value().map()?;
+            fields
+                .iter()
+                .filter(|field| {
+                    map.remove(*field)
+                        .is_some_and(|(_, field)| field.is_live(now))
+                })
+                .count()
+        };
+        self.remove_if_empty(&key);
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(removed as i32),
+        })
+    }
+
+    async fn hdel_get(self, key: Key, fields: Vec<String>) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let now = self.clock.now();
+        let values = {
+            let Some(value) = self.live_mut(&key) else {
+                return Ok(ServerResponse::Bulk { values: vec![] });
+            };
+            let map = value.value().map()?;
+            fields
+                .iter()
+                .filter_map(|field| map.ct_remove(field, now).transpose())
+                .collect::<Result<_>>()?
+        };
+        self.remove_if_empty(&key);
+        Ok(ServerResponse::Bulk { values })
     }
 
-    async fn llpush_ex(self, key: String, value: ValueType, expire: u32) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn hpop_rand(self, key: Key, count: u32) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let now = self.clock.now();
+        let values = {
+            let Some(value) = self.live_mut(&key) else {
+                return Ok(ServerResponse::Bulk { values: vec![] });
+            };
+            let map = value.value().map()?;
+            let fields = map
+                .iter()
+                .filter(|field| field.is_live(now))
+                .map(|field| field.key().clone())
+                .choose_multiple(&mut rand::thread_rng(), count as usize);
+
+            let mut values = Vec::with_capacity(fields.len() * 2);
+            for field in fields {
+                if let Some(value) = map.ct_remove(&field, now)? {
+                    values.push(ValueType::String(field));
+                    values.push(value);
+                }
+            }
+            values
+        };
+        self.remove_if_empty(&key);
+        Ok(ServerResponse::Bulk { values })
+    }
+
+    async fn hexists(self, key: Key, field: String) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let exists = self
+            .with_hash(&key, |map, now| map.ct_contains(&field, now))?
+            .unwrap_or(false);
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(exists as i32),
+        })
+    }
+
+    async fn hget(self, key: Key, field: String) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let value = self
+            .with_hash(&key, |map, now| map.ct_get(&field, now))?
+            .transpose()?
+            .flatten()
+            .unwrap_or_default();
+        Ok(ServerResponse::Single { value })
+    }
+
+    async fn hget_all(self, key: Key) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let values = self
+            .with_hash(&key, |map, now| -> Result<_> {
+                let mut values = Vec::new();
+                for field in map.iter().filter(|field| field.is_live(now)) {
+                    values.push(ValueType::String(field.key().clone()));
+                    values.push(field.value().value().to_value(now)?);
+                }
+                Ok(values)
+            })?
+            .transpose()?
+            .unwrap_or_default();
+        Ok(ServerResponse::Bulk { values })
+    }
+
+    async fn hmget(self, key: Key, fields: Vec<String>) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let values = self
+            .with_hash(&key, |map, now| {
+                fields
+                    .iter()
+                    .map(|field| Ok(map.ct_get(field, now)?.unwrap_or_default()))
+                    .collect::<Result<_>>()
+            })?
+            .transpose()?
+            .unwrap_or_else(|| vec![ValueType::None; fields.len()]);
+        Ok(ServerResponse::Bulk { values })
+    }
+
+    async fn hkeys(self, key: Key) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let values = self
+            .with_hash(&key, |map, now| {
+                map.iter()
+                    .filter(|field| field.is_live(now))
+                    .map(|field| ValueType::String(field.key().clone()))
+                    .collect()
+            })?
+            .unwrap_or_default();
+        Ok(ServerResponse::Bulk { values })
+    }
+
+    async fn hvalues(self, key: Key) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let values = self
+            .with_hash(&key, |map, now| {
+                map.iter()
+                    .filter(|field| field.is_live(now))
+                    .map(|field| field.value().value().to_value(now))
+                    .collect::<Result<_>>()
+            })?
+            .transpose()?
+            .unwrap_or_default();
+        Ok(ServerResponse::Bulk { values })
+    }
+
+    async fn hlen(self, key: Key) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let len = self
+            .with_hash(&key, |map, now| {
+                map.iter().filter(|field| field.is_live(now)).count()
+            })?
+            .unwrap_or(0);
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(len as i32),
+        })
+    }
+
+    async fn hdecr(self, key: Key, field: String) -> Result<ServerResponse> {
+        self.hdecr_by(key, field, 1).await
+    }
+
+    async fn hdecr_by(self, key: Key, field: String, by: i64) -> Result<ServerResponse> {
+        self.hash_add(key, field, by.checked_neg().ok_or(StateError::Overflow)?)
+    }
+
+    async fn hincr(self, key: Key, field: String) -> Result<ServerResponse> {
+        self.hincr_by(key, field, 1).await
+    }
+
+    async fn hincr_by(self, key: Key, field: String, by: i64) -> Result<ServerResponse> {
+        self.hash_add(key, field, by)
+    }
+
+    async fn hset(self, key: Key, field: String, value: ValueType) -> Result<ServerResponse> {
+        let value = CompositeValue::from_value_at(value, 1)?;
+        let _guard = self.shared();
+        let now = self.clock.now();
+        let entry = self.hash_or_default(key);
+        let created = entry.value().map()?.ct_insert(&field, value, now);
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(created as i32),
+        })
+    }
+
+    async fn hset_nx(self, key: Key, field: String, value: ValueType) -> Result<ServerResponse> {
+        let value = CompositeValue::from_value_at(value, 1)?;
+        let _guard = self.shared();
+        let now = self.clock.now();
+        let entry = self.hash_or_default(key);
+        let created = match entry.value().map()?.entry(field) {
+            Entry::Occupied(field) if field.get().is_live(now) => false,
+            field => {
+                field.insert(Expiring::new(value, None));
+                true
+            }
+        };
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(created as i32),
+        })
     }
 
-    async fn lrpush(self, key: String, value: ValueType) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn hset_ex(
+        self,
+        key: Key,
+        field: String,
+        value: ValueType,
+        expire: u32,
+    ) -> Result<ServerResponse> {
+        let value = CompositeValue::from_value_at(value, 1)?;
+        let _guard = self.shared();
+        let now = self.clock.now();
+        let deadline = expiry::deadline(&*self.clock, expire);
+        let entry = self.hash_or_default(key);
+        let created = entry
+            .value()
+            .map()?
+            .insert(field.clone(), Expiring::new(value, Some(deadline)))
+            .is_none_or(|old| !old.is_live(now));
+        self.track_member(entry.key().clone(), Member::Field(field), deadline);
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(created as i32),
+        })
+    }
+
+    async fn hmset(self, key: Key, fields: Vec<(String, ValueType)>) -> Result<ServerResponse> {
+        if fields.is_empty() {
+            return Ok(ServerResponse::Ok);
+        }
+        let fields = fields
+            .into_iter()
+            .map(|(field, value)| Ok((field, CompositeValue::from_value_at(value, 1)?)))
+            .collect::<Result<Vec<_>>>()?;
+        let _guard = self.shared();
+        let now = self.clock.now();
+        let entry = self.hash_or_default(key);
+        let map = entry.value().map()?;
+        for (field, value) in fields {
+            map.ct_insert(&field, value, now);
+        }
+        Ok(ServerResponse::Ok)
     }
 
-    async fn lrpush_nx(self, key: String, value: ValueType) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn hmset_nx(self, key: Key, fields: Vec<(String, ValueType)>) -> Result<ServerResponse> {
+        if fields.is_empty() {
+            return Ok(ServerResponse::Single {
+                value: ValueType::Int(1),
+            });
+        }
+        let fields = fields
+            .into_iter()
+            .map(|(field, value)| Ok((field, CompositeValue::from_value_at(value, 1)?)))
+            .collect::<Result<Vec<_>>>()?;
+        let _guard = self.shared();
+        let now = self.clock.now();
+        let entry = self.hash_or_default(key);
+        let map = entry.value().map()?;
+        if fields.iter().any(|(field, _)| map.ct_contains(field, now)) {
+            return Ok(ServerResponse::Single {
+                value: ValueType::Int(0),
+            });
+        }
+        for (field, value) in fields {
+            map.ct_insert(&field, value, now);
+        }
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1),
+        })
+    }
+
+    async fn zadd(self, key: Key, score: Score, member: String) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let now = self.clock.now();
+        let entry = self.ord_set_or_default(key);
+        let created = entry.value().ord_set()?.insert(member, score, None, now);
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(created as i32),
+        })
+    }
+
+    async fn zadd_nx(self, key: Key, score: Score, member: String) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let now = self.clock.now();
+        let entry = self.ord_set_or_default(key);
+        let created = entry.value().ord_set()?.insert_nx(member, score, now);
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(created as i32),
+        })
+    }
+
+    async fn zincr_by(self, key: Key, by: Score, member: String) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let now = self.clock.now();
+        let entry = self.ord_set_or_default(key);
+        let score = entry
+            .value()
+            .ord_set()?
+            .incr(member, by, now)
+            .ok_or(StateError::InvalidScore)?;
+        Ok(ServerResponse::OptScore { value: Some(score) })
+    }
+
+    async fn zdecr_by(self, key: Key, by: Score, member: String) -> Result<ServerResponse> {
+        self.zincr_by(key, -by, member).await
+    }
+
+    async fn zscore(self, key: Key, member: String) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let value = self
+            .with_ord_set(&key, |ord_set, now| ord_set.score(&member, now))?
+            .flatten();
+        Ok(ServerResponse::OptScore { value })
+    }
+
+    async fn zmscore(self, key: Key, members: Vec<String>) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let values = self
+            .with_ord_set(&key, |ord_set, now| {
+                members
+                    .iter()
+                    .map(|member| ord_set.score(member, now))
+                    .collect()
+            })?
+            .unwrap_or_else(|| vec![None; members.len()]);
+        Ok(ServerResponse::Scores { values })
+    }
+
+    async fn zpop_min(self, key: Key, count: u32) -> Result<ServerResponse> {
+        self.ord_set_pop(key, count, false)
+    }
+
+    async fn zpop_max(self, key: Key, count: u32) -> Result<ServerResponse> {
+        self.ord_set_pop(key, count, true)
+    }
+
+    async fn zrem(self, key: Key, member: String) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let now = self.clock.now();
+        let removed = {
+            let Some(value) = self.live_mut(&key) else {
+                return Ok(ServerResponse::Single {
+                    value: ValueType::Int(0),
+                });
+            };
+            value.value().ord_set()?.remove(&member, now).is_some()
+        };
+        self.remove_if_empty(&key);
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(removed as i32),
+        })
+    }
+
+    async fn zexpire(self, key: Key, member: String, expire: u32) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let deadline = expiry::deadline(&*self.clock, expire);
+        let expired = self
+            .with_ord_set(&key, |ord_set, now| ord_set.expire(&member, deadline, now))?
+            .unwrap_or(false);
+        if expired {
+            self.track_member(key, Member::Field(member), deadline);
+        }
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(expired as i32),
+        })
+    }
+
+    async fn zrange(self, key: Key, start: i32, stop: i32) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let values = self
+            .with_ord_set(&key, |ord_set, now| {
+                ord_set
+                    .range(start, stop, now)
+                    .into_iter()
+                    .map(|(member, _)| ValueType::String(member))
+                    .collect()
+            })?
+            .unwrap_or_default();
+        Ok(ServerResponse::Bulk { values })
+    }
+
+    async fn zrange_by_score(self, key: Key, min: Score, max: Score) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let values = self
+            .with_ord_set(&key, |ord_set, now| {
+                ord_set
+                    .range_by_score(min, max, now)
+                    .into_iter()
+                    .map(|(member, _)| ValueType::String(member))
+                    .collect()
+            })?
+            .unwrap_or_default();
+        Ok(ServerResponse::Bulk { values })
+    }
+
+    async fn zrank(self, key: Key, member: String) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let rank = self
+            .with_ord_set(&key, |ord_set, now| ord_set.rank(&member, now))?
+            .flatten();
+        Ok(ServerResponse::OptInt {
+            value: rank.map(|rank| rank as u32),
+        })
+    }
+
+    async fn zcard(self, key: Key) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let len = self
+            .with_ord_set(&key, |ord_set, now| ord_set.len(now))?
+            .unwrap_or(0);
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(len as i32),
+        })
+    }
+
+    async fn zcount(self, key: Key, min: Score, max: Score) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let count = self
+            .with_ord_set(&key, |ord_set, now| ord_set.count(min, max, now))?
+            .unwrap_or(0);
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(count as i32),
+        })
+    }
+
+    async fn llpush(self, key: Key, value: ValueType) -> Result<ServerResponse> {
+        let value = CompositeValue::from_value_at(value, 1)?;
+        let _guard = self.shared();
+        self.list_or_default(key)
+            .value()
+            .list()?
+            .push_front(Expiring::new(value, None));
+        Ok(ServerResponse::Ok)
     }
 
-    async fn lrpush_ex(self, key: String, value: ValueType, expire: u32) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn llpush_nx(self, key: Key, value: ValueType) -> Result<ServerResponse> {
+        let value = CompositeValue::from_value_at(value, 1)?;
+        let _guard = self.shared();
+        let created = self.list_nx(key, value);
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(created as i32),
+        })
+    }
+
+    async fn llpush_ex(self, key: Key, value: ValueType, expire: u32) -> Result<ServerResponse> {
+        let value = CompositeValue::from_value_at(value, 1)?;
+        let _guard = self.shared();
+        let deadline = expiry::deadline(&*self.clock, expire);
+        let entry = self.list_or_default(key);
+        entry
+            .value()
+            .list()?
+            .push_front(Expiring::new(value, Some(deadline)));
+        self.track_member(entry.key().clone(), Member::Item, deadline);
+        Ok(ServerResponse::Ok)
     }
 
-    async fn lexpire(self, key: String, index: u32, expire: u32) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn lrpush(self, key: Key, value: ValueType) -> Result<ServerResponse> {
+        let value = CompositeValue::from_value_at(value, 1)?;
+        let _guard = self.shared();
+        self.list_or_default(key)
+            .value()
+            .list()?
+            .push_back(Expiring::new(value, None));
+        Ok(ServerResponse::Ok)
     }
 
-    async fn llpop(self, key: String, count: u32) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn lrpush_nx(self, key: Key, value: ValueType) -> Result<ServerResponse> {
+        let value = CompositeValue::from_value_at(value, 1)?;
+        let _guard = self.shared();
+        let created = self.list_nx(key, value);
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(created as i32),
+        })
+    }
+
+    async fn lrpush_ex(self, key: Key, value: ValueType, expire: u32) -> Result<ServerResponse> {
+        let value = CompositeValue::from_value_at(value, 1)?;
+        let _guard = self.shared();
+        let deadline = expiry::deadline(&*self.clock, expire);
+        let entry = self.list_or_default(key);
+        entry
+            .value()
+            .list()?
+            .push_back(Expiring::new(value, Some(deadline)));
+        self.track_member(entry.key().clone(), Member::Item, deadline);
+        Ok(ServerResponse::Ok)
     }
 
-    async fn lrpop(self, key: String, count: u32) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn lexpire(self, key: Key, index: u32, expire: u32) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let now = self.clock.now();
+        let Some(value) = self.live(&key) else {
+            return Ok(ServerResponse::Single {
+                value: ValueType::Int(0),
+            });
+        };
+        let list = value.value().list()?;
+        let Some(item) = list
+            .iter()
+            .filter(|item| item.is_live(now))
+            .nth(index as usize)
+        else {
+            return Ok(ServerResponse::Single {
+                value: ValueType::Int(0),
+            });
+        };
+        let deadline = expiry::deadline(&*self.clock, expire);
+        item.set_deadline(Some(deadline));
+        self.track_member(key, Member::Item, deadline);
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1),
+        })
     }
 
-    async fn lrange(self, key: String, start: u32, end: u32) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn llpop(self, key: Key, count: u32) -> Result<ServerResponse> {
+        self.list_pop(key, count, false)
     }
 
-    async fn sadd(self, key: String, members: Vec<String>) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn lrpop(self, key: Key, count: u32) -> Result<ServerResponse> {
+        self.list_pop(key, count, true)
     }
 
-    async fn sadd_nx(self, key: String, members: Vec<String>) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn lrange(self, key: Key, start: u32, end: u32) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let now = self.clock.now();
+        let Some(value) = self.live(&key) else {
+            return Ok(ServerResponse::Bulk { values: vec![] });
+        };
+        let values = if start > end {
+            vec![]
+        } else {
+            value
+                .value()
+                .list()?
+                .iter()
+                .filter(|item| item.is_live(now))
+                .skip(start as usize)
+                .take((end - start) as usize + 1)
+                .map(|item| item.value().to_value(now))
+                .collect::<Result<_>>()?
+        };
+        Ok(ServerResponse::Bulk { values })
+    }
+
+    async fn sadd(self, key: Key, members: Vec<String>) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let now = self.clock.now();
+        let entry = self.set_or_default(key);
+        let set = entry.value().set()?;
+        let added = members
+            .into_iter()
+            .filter(|member| {
+                set.insert(ValueType::String(member.clone()), Expiring::new((), None))
+                    .is_none_or(|old| !old.is_live(now))
+            })
+            .count();
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(added as i32),
+        })
+    }
+
+    async fn sadd_nx(self, key: Key, members: Vec<String>) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let now = self.clock.now();
+        let entry = self.set_or_default(key);
+        let set = entry.value().set()?;
+        let mut added = 0;
+        for member in members {
+            match set.entry(ValueType::String(member)) {
+                Entry::Occupied(existing) if existing.get().is_live(now) => {}
+                Entry::Occupied(mut expired) => {
+                    expired.insert(Expiring::new((), None));
+                    added += 1;
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(Expiring::new((), None));
+                    added += 1;
+                }
+            }
+        }
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(added),
+        })
+    }
+
+    async fn sadd_ex(self, key: Key, member: String, expire: u32) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let now = self.clock.now();
+        let deadline = expiry::deadline(&*self.clock, expire);
+        let entry = self
+            .live_entry(key)
+            .or_insert_with(|| CompositeValue::Set(Default::default()));
+        let member = ValueType::String(member);
+        let created = entry
+            .value()
+            .set()?
+            .insert(member.clone(), Expiring::new((), Some(deadline)))
+            .is_none_or(|old| !old.is_live(now));
+        self.track_member(entry.key().clone(), Member::Value(member), deadline);
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(created as i32),
+        })
+    }
+
+    async fn smember(self, key: Key, member: String) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let present = self
+            .with_set(&key, |set, now| {
+                set.get(&ValueType::String(member))
+                    .is_some_and(|member| member.is_live(now))
+            })?
+            .unwrap_or(false);
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(present as i32),
+        })
+    }
+
+    async fn smembers(self, key: Key) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let values = self.set_members(&key)?.into_iter().collect();
+        Ok(ServerResponse::Bulk { values })
+    }
+
+    async fn sexpire(self, key: Key, member: String, expire: u32) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let now = self.clock.now();
+        let Some(value) = self.live(&key) else {
+            return Ok(ServerResponse::Single {
+                value: ValueType::Int(0),
+            });
+        };
+        let set = value.value().set()?;
+        let Some(member) = set
+            .get(&ValueType::String(member))
+            .filter(|member| member.is_live(now))
+        else {
+            return Ok(ServerResponse::Single {
+                value: ValueType::Int(0),
+            });
+        };
+        let deadline = expiry::deadline(&*self.clock, expire);
+        member.set_deadline(Some(deadline));
+        self.track_member(key, Member::Value(member.key().clone()), deadline);
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1),
+        })
+    }
+
+    async fn srem(self, key: Key, members: Vec<String>) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let now = self.clock.now();
+        let removed = {
+            let Some(value) = self.live_mut(&key) else {
+                return Ok(ServerResponse::Single {
+                    value: ValueType::Int(0),
+                });
+            };
+            let set = value.value().set()?;
+            members
+                .into_iter()
+                .filter_map(|member| set.remove(&ValueType::String(member)))
+                .filter(|(_, member)| member.is_live(now))
+                .count()
+        };
+        self.remove_if_empty(&key);
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(removed as i32),
+        })
+    }
+
+    async fn spop(self, key: Key, count: u32) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let now = self.clock.now();
+        let values = {
+            let Some(value) = self.live_mut(&key) else {
+                return Ok(ServerResponse::Bulk { values: vec![] });
+            };
+            let set = value.value().set()?;
+            let members = set
+                .iter()
+                .filter(|member| member.is_live(now))
+                .map(|member| member.key().clone())
+                .choose_multiple(&mut rand::thread_rng(), count as usize);
+            members
+                .into_iter()
+                .filter(|member| set.remove(member).is_some())
+                .collect()
+        };
+        self.remove_if_empty(&key);
+        Ok(ServerResponse::Bulk { values })
+    }
+
+    async fn scard(self, key: Key) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let len = self
+            .with_set(&key, |set, now| {
+                set.iter().filter(|member| member.is_live(now)).count()
+            })?
+            .unwrap_or(0);
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(len as i32),
+        })
+    }
+
+    async fn srand_member(self, key: Key, count: u32) -> Result<ServerResponse> {
+        let _guard = self.shared();
+        let values = self
+            .with_set(&key, |set, now| {
+                set.iter()
+                    .filter(|member| member.is_live(now))
+                    .map(|member| member.key().clone())
+                    .choose_multiple(&mut rand::thread_rng(), count as usize)
+            })?
+            .unwrap_or_default();
+        Ok(ServerResponse::Bulk { values })
+    }
+
+    async fn smove(self, source: Key, destination: Key, member: String) -> Result<ServerResponse> {
+        let _guard = self.exclusive();
+        let now = self.clock.now();
+        let member = ValueType::String(member);
+        // both keys are checked before anything moves so a bad destination leaves the source as is
+        let Some(source_set) = self
+            .live(&source)
+            .map(|value| value.value().set())
+            .transpose()?
+        else {
+            return Ok(ServerResponse::Single {
+                value: ValueType::Int(0),
+            });
+        };
+        if let Some(value) = self.live(&destination) {
+            value.value().set()?;
+        }
+        let moved = source_set
+            .remove(&member)
+            .filter(|(_, expiring)| expiring.is_live(now));
+        if let Some((member, expiring)) = &moved {
+            // the member keeps its deadline in its new set
+            if let Some(deadline) = expiring.deadline() {
+                self.track_member(destination.clone(), Member::Value(member.clone()), deadline);
+            }
+            self.set_or_default(destination)
+                .value()
+                .set()?
+                .insert(member.clone(), expiring.clone());
+        }
+        self.remove_if_empty(&source);
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(moved.is_some() as i32),
+        })
     }
 
-    async fn sadd_ex(self, key: String, member: String, expire: u32) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn sunion(self, keys: Vec<Key>) -> Result<ServerResponse> {
+        let _guard = self.exclusive();
+        let values = self.sunion_members(&keys)?.into_iter().collect();
+        Ok(ServerResponse::Bulk { values })
     }
 
-    async fn smember(self, key: String, member: String) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn sinter(self, keys: Vec<Key>) -> Result<ServerResponse> {
+        let _guard = self.exclusive();
+        let values = self.sinter_members(&keys)?.into_iter().collect();
+        Ok(ServerResponse::Bulk { values })
     }
 
-    async fn smembers(self, key: String) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn sdiff(self, keys: Vec<Key>) -> Result<ServerResponse> {
+        let _guard = self.exclusive();
+        let values = self.sdiff_members(&keys)?.into_iter().collect();
+        Ok(ServerResponse::Bulk { values })
     }
 
-    async fn sexpire(self, key: String, member: String, expire: u32) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn sunion_store(self, destination: Key, keys: Vec<Key>) -> Result<ServerResponse> {
+        let _guard = self.exclusive();
+        let members = self.sunion_members(&keys)?;
+        Ok(self.store_set(destination, members))
     }
 
-    async fn srem(self, key: String, members: Vec<String>) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn sinter_store(self, destination: Key, keys: Vec<Key>) -> Result<ServerResponse> {
+        let _guard = self.exclusive();
+        let members = self.sinter_members(&keys)?;
+        Ok(self.store_set(destination, members))
     }
 
-    async fn spop(self, key: String, count: u32) -> Result<ServerResponse> {
-        unimplemented!()
+    async fn sdiff_store(self, destination: Key, keys: Vec<Key>) -> Result<ServerResponse> {
+        let _guard = self.exclusive();
+        let members = self.sdiff_members(&keys)?;
+        Ok(self.store_set(destination, members))
     }
 }
 
 #[derive(Clone)]
 pub enum CompositeValue {
     Value(ValueType),
-    List(Arc<ArcSwapLinkedList<ValueType>>),
-    Set(Arc<DashSet<ValueType>>),
-    Map(Arc<DashMap<String, ValueType>>),
-    OrdSet(Arc<BzTree<String, isize>>),
+    // lists and maps nest, sets and sorted sets only ever sit at the top of a key
+    List(Arc<ArcSwapLinkedList<Expiring<CompositeValue>>>),
+    Set(Arc<DashMap<ValueType, Expiring<()>>>),
+    Map(Arc<DashMap<String, Expiring<CompositeValue>>>),
+    OrdSet(Arc<SortedSet>),
 }
 
 impl CompositeValue {
@@ -372,30 +1620,250 @@ impl CompositeValue {
         }
     }
 
-    pub fn list(&self) -> Result<Arc<ArcSwapLinkedList<ValueType>>> {
+    pub fn list(&self) -> Result<Arc<ArcSwapLinkedList<Expiring<CompositeValue>>>> {
         match self {
             CompositeValue::List(list) => Ok(list.clone()),
             _ => Err(StateError::BadKeyType)?,
         }
     }
-    pub fn set(&self) -> Result<Arc<DashSet<ValueType>>> {
+
+    pub fn set(&self) -> Result<Arc<DashMap<ValueType, Expiring<()>>>> {
         match self {
             CompositeValue::Set(set) => Ok(set.clone()),
             _ => Err(StateError::BadKeyType)?,
         }
     }
 
-    pub fn map(&self) -> Result<Arc<DashMap<String, ValueType>>> {
+    pub fn map(&self) -> Result<Arc<DashMap<String, Expiring<CompositeValue>>>> {
         match self {
             CompositeValue::Map(map) => Ok(map.clone()),
             _ => Err(StateError::BadKeyType)?,
         }
     }
 
-    pub fn ord_set(&self) -> Result<Arc<BzTree<String, isize>>> {
+    pub fn ord_set(&self) -> Result<Arc<SortedSet>> {
         match self {
             CompositeValue::OrdSet(ord_set) => Ok(ord_set.clone()),
             _ => Err(StateError::BadKeyType)?,
         }
     }
+
+    /// Builds the stored form of `value`, nested lists and maps become collections of their own.
+    pub fn from_value(value: ValueType) -> Result<Self> {
+        Self::from_value_at(value, 0)
+    }
+
+    // `depth` counts the collections the value is stored below
+    fn from_value_at(value: ValueType, depth: usize) -> Result<Self> {
+        Ok(match value {
+            ValueType::List(values) => {
+                check_depth(depth)?;
+                let list = ArcSwapLinkedList::default();
+                for value in values {
+                    list.push_back(Expiring::new(Self::from_value_at(value, depth + 1)?, None));
+                }
+                CompositeValue::List(Arc::new(list))
+            }
+            ValueType::Map(fields) => {
+                check_depth(depth)?;
+                CompositeValue::Map(Arc::new(
+                    fields
+                        .into_iter()
+                        .map(|(field, value)| {
+                            let value = Self::from_value_at(value, depth + 1)?;
+                            Ok((field, Expiring::new(value, None)))
+                        })
+                        .collect::<Result<_>>()?,
+                ))
+            }
+            value => CompositeValue::Value(value),
+        })
+    }
+
+    /// Snapshots the whole subtree, members past their deadline are left out.
+    pub fn to_value(&self, now: Instant) -> Result<ValueType> {
+        self.to_value_nested(now, 0)
+    }
+
+    fn to_value_nested(&self, now: Instant, depth: usize) -> Result<ValueType> {
+        match self {
+            CompositeValue::Value(value) => Ok(value.clone()),
+            CompositeValue::List(list) => {
+                check_depth(depth)?;
+                Ok(ValueType::List(
+                    list.iter()
+                        .filter(|item| item.is_live(now))
+                        .map(|item| item.value().to_value_nested(now, depth + 1))
+                        .collect::<Result<_>>()?,
+                ))
+            }
+            CompositeValue::Map(map) => {
+                check_depth(depth)?;
+                Ok(ValueType::Map(
+                    map.iter()
+                        .filter(|field| field.is_live(now))
+                        .map(|field| {
+                            let value = field.value().value().to_value_nested(now, depth + 1)?;
+                            Ok((field.key().clone(), value))
+                        })
+                        .collect::<Result<_>>()?,
+                ))
+            }
+            CompositeValue::Set(_) | CompositeValue::OrdSet(_) => {
+                Err(StateError::CannotReturnKeyType)?
+            }
+        }
+    }
+
+    // drops the `due` members which are past their deadline, each may have been given a later one
+    // since it was recorded
+    fn reclaim(&self, due: &[Member], now: Instant) {
+        for member in due {
+            match (self, member) {
+                (CompositeValue::Map(map), Member::Field(field)) => {
+                    map.remove_if(field, |_, field| !field.is_live(now));
+                }
+                (CompositeValue::OrdSet(ord_set), Member::Field(member)) => {
+                    ord_set.remove_expired(member, now);
+                }
+                (CompositeValue::Set(set), Member::Value(member)) => {
+                    set.remove_if(member, |_, member| !member.is_live(now));
+                }
+                // one pass over the list covers every item due
+                (CompositeValue::List(list), Member::Item) => {
+                    list.retain(|item| item.is_live(now));
+                    return;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // whether anything is left which hasn't passed its deadline
+    fn has_live(&self, now: Instant) -> bool {
+        match self {
+            CompositeValue::Value(_) => true,
+            CompositeValue::List(list) => list.iter().any(|item| item.is_live(now)),
+            CompositeValue::Set(set) => set.iter().any(|member| member.is_live(now)),
+            CompositeValue::Map(map) => map.iter().any(|field| field.is_live(now)),
+            CompositeValue::OrdSet(ord_set) => ord_set.has_live(now),
+        }
+    }
+
+    // an empty collection which `instruction` can step into
+    fn container_for(instruction: &Instruction) -> Self {
+        match instruction {
+            Instruction::Hash(_) => CompositeValue::Map(Default::default()),
+            Instruction::Index(_) => CompositeValue::List(Default::default()),
+            Instruction::Member(_) => CompositeValue::Set(Default::default()),
+        }
+    }
+
+    // the live value one step down from this one, set members are returned as plain values
+    fn child(&self, instruction: &Instruction, now: Instant) -> Result<Option<CompositeValue>> {
+        Ok(match instruction {
+            Instruction::Hash(field) => self
+                .map()?
+                .get(field)
+                .filter(|field| field.is_live(now))
+                .map(|field| field.value().value().clone()),
+            Instruction::Index(index) => self
+                .list()?
+                .iter()
+                .filter(|item| item.is_live(now))
+                .nth(*index as usize)
+                .map(|item| item.value().clone()),
+            Instruction::Member(member) => {
+                let member = ValueType::String(member.clone());
+                self.set()?
+                    .get(&member)
+                    .filter(|expiring| expiring.is_live(now))
+                    .map(|_| CompositeValue::Value(member))
+            }
+        })
+    }
+
+    // resolves `path` to the subtree it points at, missing fields, indexes and members read as None
+    fn get_path(&self, path: &[Instruction], now: Instant) -> Result<ValueType> {
+        let Some((instruction, rest)) = path.split_first() else {
+            return self.to_value(now);
+        };
+        match self.child(instruction, now)? {
+            Some(child) => child.get_path(rest, now),
+            None => Ok(ValueType::None),
+        }
+    }
+
+    // writes `value` at the end of `path`, missing hashes along the way are created but list
+    // indexes must already exist
+    fn set_path(&self, path: &[Instruction], value: CompositeValue, now: Instant) -> Result<()> {
+        match path {
+            [] => Err(StateError::InvalidPath)?,
+            [Instruction::Hash(field)] => {
+                self.map()?.ct_insert(field, value, now);
+                Ok(())
+            }
+            [Instruction::Index(index)] => self.list()?.rebuild(|items| {
+                let item = items
+                    .iter_mut()
+                    .filter(|item| item.is_live(now))
+                    .nth(*index as usize)
+                    .ok_or(StateError::IndexOutOfRange)?;
+                *item = Expiring::new(value, None);
+                Ok(())
+            }),
+            [Instruction::Member(_)] => Err(StateError::InvalidPath)?,
+            [instruction, rest @ ..] => match (self.child(instruction, now)?, instruction) {
+                (Some(child), _) => child.set_path(rest, value, now),
+                (None, Instruction::Hash(field)) => {
+                    // only attached once the write below it succeeded
+                    let child = CompositeValue::container_for(&rest[0]);
+                    child.set_path(rest, value, now)?;
+                    self.map()?
+                        .insert(field.clone(), Expiring::new(child, None));
+                    Ok(())
+                }
+                (None, Instruction::Index(_)) => Err(StateError::IndexOutOfRange)?,
+                (None, Instruction::Member(_)) => Err(StateError::InvalidPath)?,
+            },
+        }
+    }
+
+    // removes whatever `path` points at, returning whether anything was there
+    fn del_path(&self, path: &[Instruction], now: Instant) -> Result<bool> {
+        match path {
+            [] => Err(StateError::InvalidPath)?,
+            [Instruction::Hash(field)] => Ok(self
+                .map()?
+                .remove(field)
+                .is_some_and(|(_, field)| field.is_live(now))),
+            [Instruction::Index(index)] => Ok(self.list()?.rebuild(|items| {
+                let position = items
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, item)| item.is_live(now))
+                    .nth(*index as usize)
+                    .map(|(position, _)| position);
+                position.map(|position| items.remove(position)).is_some()
+            })),
+            [Instruction::Member(member)] => Ok(self
+                .set()?
+                .remove(&ValueType::String(member.clone()))
+                .is_some_and(|(_, expiring)| expiring.is_live(now))),
+            [instruction, rest @ ..] => match self.child(instruction, now)? {
+                Some(child) => child.del_path(rest, now),
+                None => Ok(false),
+            },
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match self {
+            CompositeValue::Value(_) => false,
+            CompositeValue::List(list) => list.iter().next().is_none(),
+            CompositeValue::Set(set) => set.is_empty(),
+            CompositeValue::Map(map) => map.is_empty(),
+            CompositeValue::OrdSet(ord_set) => ord_set.is_empty(),
+        }
+    }
 }
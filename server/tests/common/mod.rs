@@ -0,0 +1,22 @@
+use driver::Client;
+use packets::frame;
+use server::{connection, state};
+use tokio::net::TcpListener;
+
+/// A client connected to a fresh server that takes a single connection.
+pub async fn connect() -> Client {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (stream, addr) = listener.accept().await.unwrap();
+        connection::handle(
+            state::State::default(),
+            stream,
+            addr,
+            frame::DEFAULT_MAX_FRAME_SIZE,
+        )
+        .await
+        .unwrap()
+    });
+    Client::connect(&addr.to_string()).await.unwrap()
+}
@@ -0,0 +1,361 @@
+use errors::{InfernoError, PacketsError};
+use packets::envelope::Envelope;
+use packets::frame;
+use packets::handshake::{Capabilities, Hello, PROTOCOL_VERSION};
+use packets::value::ValueType;
+use packets::{ClientCommand, ClientCommandExecutor, Packet, ServerResponse};
+use server::{connection, state};
+use std::collections::HashMap;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+const MAX_FRAME_SIZE: u32 = 1024;
+
+async fn accept() -> (TcpStream, JoinHandle<errors::Result<()>>) {
+    accept_with(state::State::default()).await
+}
+
+async fn accept_with(state: state::State) -> (TcpStream, JoinHandle<errors::Result<()>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = tokio::spawn(async move {
+        let (stream, addr) = listener.accept().await.unwrap();
+        connection::handle(state, stream, addr, MAX_FRAME_SIZE)
+            .await
+            .unwrap()
+    });
+    (TcpStream::connect(addr).await.unwrap(), server)
+}
+
+async fn connect() -> (TcpStream, JoinHandle<errors::Result<()>>) {
+    connect_with(state::State::default()).await
+}
+
+async fn connect_with(state: state::State) -> (TcpStream, JoinHandle<errors::Result<()>>) {
+    let (mut stream, server) = accept_with(state).await;
+    frame::write_packet(&mut stream, &Hello::default())
+        .await
+        .unwrap();
+    let hello: Hello = frame::read_packet(&mut stream, MAX_FRAME_SIZE)
+        .await
+        .unwrap();
+    assert_eq!(hello, Hello::default());
+    (stream, server)
+}
+
+async fn send(stream: &mut TcpStream, id: u32, command: ClientCommand) {
+    frame::write_packet(stream, &Envelope::new(id, command))
+        .await
+        .unwrap();
+}
+
+async fn receive(stream: &mut TcpStream) -> Envelope<ServerResponse> {
+    frame::read_packet(stream, MAX_FRAME_SIZE).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_response_ids_echoed() {
+    let (mut stream, _server) = connect().await;
+    send(
+        &mut stream,
+        7,
+        ClientCommand::Set {
+            key: "test".into(),
+            path: vec![],
+            value: ValueType::Int(1),
+        },
+    )
+    .await;
+    let response = receive(&mut stream).await;
+    assert_eq!(response.id, 7);
+    assert!(matches!(response.packet, ServerResponse::Ok));
+
+    let requests = [(3, "test"), (u32::MAX, "missing"), (0, "test")];
+    for (id, key) in requests {
+        send(
+            &mut stream,
+            id,
+            ClientCommand::Get {
+                key: key.into(),
+                path: vec![],
+            },
+        )
+        .await;
+    }
+
+    let mut responses = HashMap::new();
+    for _ in requests {
+        let response = receive(&mut stream).await;
+        responses.insert(response.id, response.packet);
+    }
+    for (id, key) in requests {
+        let expected = if key == "test" {
+            ValueType::Int(1)
+        } else {
+            ValueType::None
+        };
+        assert!(matches!(
+            &responses[&id],
+            ServerResponse::Single { value } if *value == expected
+        ));
+    }
+}
+
+#[tokio::test]
+async fn test_error_keeps_id() {
+    let (mut stream, _server) = connect().await;
+    send(
+        &mut stream,
+        42,
+        ClientCommand::MSet {
+            keys: vec!["a".into(), "b".into()],
+            values: vec![ValueType::Int(1)],
+        },
+    )
+    .await;
+    let response = receive(&mut stream).await;
+    assert_eq!(response.id, 42);
+    assert!(matches!(response.packet, ServerResponse::Error { .. }));
+}
+
+#[tokio::test]
+async fn test_commands_run_in_order() {
+    // sent without waiting, every read must see the write queued just before it
+    let (mut stream, _server) = connect().await;
+    let mut pipeline = Vec::new();
+    for i in 0..500 {
+        let set = ClientCommand::Set {
+            key: "test".into(),
+            path: vec![],
+            value: ValueType::Int(i),
+        };
+        let get = ClientCommand::Get {
+            key: "test".into(),
+            path: vec![],
+        };
+        for (id, command) in [(2 * i as u32, set), (2 * i as u32 + 1, get)] {
+            let mut payload = Vec::new();
+            Envelope::new(id, command)
+                .write(&mut payload)
+                .await
+                .unwrap();
+            frame::write_frame(&mut pipeline, &payload).await.unwrap();
+        }
+    }
+    stream.write_all(&pipeline).await.unwrap();
+
+    for i in 0..500 {
+        let set = receive(&mut stream).await;
+        assert_eq!(set.id, 2 * i as u32);
+        let get = receive(&mut stream).await;
+        assert_eq!(get.id, 2 * i as u32 + 1);
+        assert!(matches!(
+            get.packet,
+            ServerResponse::Single { value } if value == ValueType::Int(i)
+        ));
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_slow_command_not_blocking() {
+    // counting a large set takes a while, a read of another key sent after it shouldn't wait
+    let state = state::State::default();
+    let members = (0..1_000_000).map(|member| member.to_string()).collect();
+    state.clone().sadd("large".into(), members).await.unwrap();
+    let (mut stream, _server) = connect_with(state).await;
+
+    let mut pipeline = Vec::new();
+    let commands = [
+        (
+            0,
+            ClientCommand::SCard {
+                key: "large".into(),
+            },
+        ),
+        (
+            1,
+            ClientCommand::Get {
+                key: "test".into(),
+                path: vec![],
+            },
+        ),
+    ];
+    for (id, command) in commands {
+        let mut payload = Vec::new();
+        Envelope::new(id, command)
+            .write(&mut payload)
+            .await
+            .unwrap();
+        frame::write_frame(&mut pipeline, &payload).await.unwrap();
+    }
+    stream.write_all(&pipeline).await.unwrap();
+
+    assert_eq!(receive(&mut stream).await.id, 1);
+    let count = receive(&mut stream).await;
+    assert_eq!(count.id, 0);
+    assert!(matches!(
+        count.packet,
+        ServerResponse::Single { value } if value == ValueType::Int(1_000_000)
+    ));
+}
+
+#[tokio::test]
+async fn test_oversized_frame_rejected() {
+    let (mut stream, server) = accept().await;
+    stream.write_u32(MAX_FRAME_SIZE + 1).await.unwrap();
+
+    let result = server.await.unwrap();
+    assert!(matches!(
+        result,
+        Err(InfernoError::Packets(PacketsError::LengthExceeded { length, limit }))
+            if length == MAX_FRAME_SIZE as usize + 1 && limit == MAX_FRAME_SIZE as usize
+    ));
+}
+
+#[tokio::test]
+async fn test_oversized_collection_rejected() {
+    // a small frame claiming a huge key list, the count follows the id and opcode
+    let mut payload = Vec::new();
+    Envelope::new(
+        0,
+        ClientCommand::MGet {
+            keys: vec!["a".into()],
+        },
+    )
+    .write(&mut payload)
+    .await
+    .unwrap();
+    payload[5..9].copy_from_slice(&u32::MAX.to_be_bytes());
+
+    let (mut stream, server) = connect().await;
+    frame::write_frame(&mut stream, &payload).await.unwrap();
+
+    let result = server.await.unwrap();
+    assert!(matches!(
+        result,
+        Err(InfernoError::Packets(PacketsError::LengthExceeded { length, .. }))
+            if length == u32::MAX as usize
+    ));
+}
+
+#[tokio::test]
+async fn test_truncated_bytes_rejected() {
+    // claims a long string but ends early, nothing is allocated up front
+    let mut payload = Vec::new();
+    payload.extend_from_slice(&frame::MAX_BYTES_LENGTH.to_be_bytes());
+    payload.extend_from_slice(b"short");
+    let result = String::read(&mut payload.as_slice()).await;
+    assert!(matches!(result, Err(InfernoError::Io(_))));
+}
+
+#[tokio::test]
+async fn test_version_mismatch() {
+    let (mut stream, server) = accept().await;
+    let hello = Hello {
+        version: PROTOCOL_VERSION + 1,
+        capabilities: Capabilities::SUPPORTED,
+    };
+    frame::write_packet(&mut stream, &hello).await.unwrap();
+
+    let reply: Hello = frame::read_packet(&mut stream, MAX_FRAME_SIZE)
+        .await
+        .unwrap();
+    assert_eq!(reply.version, PROTOCOL_VERSION);
+    assert!(matches!(
+        hello.negotiate(&reply),
+        Err(InfernoError::Packets(PacketsError::VersionMismatch { .. }))
+    ));
+
+    let result = server.await.unwrap();
+    assert!(matches!(
+        result,
+        Err(InfernoError::Packets(PacketsError::VersionMismatch { local, remote }))
+            if local == PROTOCOL_VERSION && remote == PROTOCOL_VERSION + 1
+    ));
+}
+
+#[tokio::test]
+async fn test_missing_capabilities() {
+    let (mut stream, server) = accept().await;
+    let hello = Hello::new(Capabilities::COMPRESSION);
+    frame::write_packet(&mut stream, &hello).await.unwrap();
+
+    let reply: Hello = frame::read_packet(&mut stream, MAX_FRAME_SIZE)
+        .await
+        .unwrap();
+    assert_eq!(reply.capabilities, Capabilities::SUPPORTED);
+
+    let result = server.await.unwrap();
+    assert!(matches!(
+        result,
+        Err(InfernoError::Packets(PacketsError::MissingCapabilities(_)))
+    ));
+}
+
+#[tokio::test]
+async fn test_capabilities_intersected() {
+    let local = Hello::new(Capabilities::REQUEST_IDS | Capabilities::PUSH_MESSAGES);
+    let remote = Hello::new(Capabilities::REQUEST_IDS | Capabilities::COMPRESSION);
+    let agreed = local.negotiate(&remote).unwrap();
+    assert_eq!(agreed.capabilities, Capabilities::REQUEST_IDS);
+}
+
+#[tokio::test]
+async fn test_command_before_handshake() {
+    let (mut stream, server) = accept().await;
+    send(
+        &mut stream,
+        0,
+        ClientCommand::Get {
+            key: "test".into(),
+            path: vec![],
+        },
+    )
+    .await;
+
+    let result = server.await.unwrap();
+    assert!(matches!(
+        result,
+        Err(InfernoError::Packets(PacketsError::HandshakeExpected))
+    ));
+}
+
+#[tokio::test]
+async fn test_opcodes_stable() {
+    let cases = [
+        (
+            ClientCommand::Expire {
+                key: "a".into(),
+                expire: 1,
+            },
+            0,
+        ),
+        (
+            ClientCommand::Get {
+                key: "a".into(),
+                path: vec![],
+            },
+            8,
+        ),
+        (
+            ClientCommand::SDiffStore {
+                destination: "a".into(),
+                keys: vec![],
+            },
+            79,
+        ),
+    ];
+    for (command, opcode) in cases {
+        let mut buffer = Vec::new();
+        command.write(&mut buffer).await.unwrap();
+        assert_eq!(buffer[0], opcode, "{:?}", command);
+    }
+
+    let mut buffer = Vec::new();
+    ServerResponse::ScoredBulk { values: vec![] }
+        .write(&mut buffer)
+        .await
+        .unwrap();
+    assert_eq!(buffer[0], 8);
+}
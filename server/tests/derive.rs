@@ -0,0 +1,105 @@
+use errors::{InfernoError, PacketsError};
+use packets::envelope::Envelope;
+use packets::Packet;
+
+#[derive(Debug, PartialEq, Packet)]
+struct Named {
+    id: u32,
+    name: String,
+    #[packet(skip)]
+    cached: Option<u32>,
+}
+
+#[derive(Debug, PartialEq, Packet)]
+struct Tuple(i32, #[packet(with = "flag")] bool);
+
+#[derive(Debug, PartialEq, Packet)]
+enum Message<T> {
+    #[packet(tag = 7)]
+    Empty,
+    #[packet(tag = 3)]
+    One(T),
+    #[packet(tag = 200)]
+    Many { values: Vec<T>, total: i64 },
+}
+
+mod flag {
+    use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+    pub async fn write<W>(value: &bool, stream: &mut W) -> errors::Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        stream.write_u8(if *value { b'y' } else { b'n' }).await?;
+        Ok(())
+    }
+
+    pub async fn read<R>(stream: &mut R) -> errors::Result<bool>
+    where
+        R: AsyncRead + Unpin,
+    {
+        Ok(stream.read_u8().await? == b'y')
+    }
+}
+
+async fn encode<T: Packet>(packet: &T) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    packet.write(&mut buffer).await.unwrap();
+    buffer
+}
+
+async fn decode<T: Packet>(buffer: &[u8]) -> errors::Result<T> {
+    T::read(&mut &buffer[..]).await
+}
+
+#[tokio::test]
+async fn test_struct_skips_fields() {
+    let named = Named {
+        id: 1,
+        name: "a".into(),
+        cached: Some(5),
+    };
+    let buffer = encode(&named).await;
+    assert_eq!(buffer, [0, 0, 0, 1, 0, 0, 0, 1, b'a']);
+
+    let read: Named = decode(&buffer).await.unwrap();
+    assert_eq!(
+        read,
+        Named {
+            cached: None,
+            ..named
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_custom_codec() {
+    let buffer = encode(&Tuple(-1, true)).await;
+    assert_eq!(buffer, [0xff, 0xff, 0xff, 0xff, b'y']);
+    assert_eq!(decode::<Tuple>(&buffer).await.unwrap(), Tuple(-1, true));
+}
+
+#[tokio::test]
+async fn test_enum_tags() {
+    assert_eq!(encode(&Message::<u32>::Empty).await, [7]);
+    assert_eq!(encode(&Message::One(2u32)).await, [3, 0, 0, 0, 2]);
+
+    let many = Message::Many {
+        values: vec![Envelope::new(1, 2u32)],
+        total: 1,
+    };
+    let buffer = encode(&many).await;
+    assert_eq!(buffer[0], 200);
+    let Message::Many { values, total } = decode::<Message<Envelope<u32>>>(&buffer).await.unwrap()
+    else {
+        panic!("Expected many");
+    };
+    assert_eq!(total, 1);
+    assert_eq!((values[0].id, values[0].packet), (1, 2u32));
+
+    let unknown = decode::<Message<u32>>(&[4]).await;
+    assert!(matches!(
+        unknown,
+        Err(InfernoError::Packets(PacketsError::UnknownPacketType(4)))
+    ));
+}
@@ -0,0 +1,559 @@
+mod common;
+
+use common::connect;
+use driver::prelude::*;
+use errors::InfernoError;
+use packets::envelope::Envelope;
+use packets::frame;
+use packets::handshake::Hello;
+use server::{connection, state};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio::task::JoinHandle;
+
+fn single(response: &errors::Result<ServerResponse>) -> &ValueType {
+    match response {
+        Ok(ServerResponse::Single { value }) => value,
+        other => panic!("Expected single response, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_pipeline_in_order() {
+    let mut client = connect().await;
+
+    let mut pipeline = client.pipeline();
+    for index in 0..50 {
+        pipeline.set(
+            format!("key{}", index).into(),
+            vec![],
+            ValueType::Int(index),
+        );
+    }
+    assert_eq!(pipeline.len(), 50);
+    assert!(pipeline.execute().await.iter().all(Result::is_ok));
+
+    let mut pipeline = client.pipeline();
+    for index in 0..50 {
+        pipeline.get(format!("key{}", index).into(), vec![]);
+    }
+    let results = pipeline.execute().await;
+    assert_eq!(results.len(), 50);
+    for (index, result) in results.iter().enumerate() {
+        assert_eq!(single(result), &ValueType::Int(index as i32));
+    }
+
+    // the client keeps working after a pipeline
+    let value = client.get("key7".into(), vec![]).await;
+    assert_eq!(single(&value), &ValueType::Int(7));
+}
+
+#[tokio::test]
+async fn test_pipeline_errors_per_entry() {
+    let mut client = connect().await;
+
+    client
+        .set("name".into(), vec![], ValueType::String("inferno".into()))
+        .await
+        .unwrap();
+
+    let mut pipeline = client.pipeline();
+    pipeline
+        .incr_by("counter".into(), 2)
+        .lrpush("name".into(), ValueType::Int(1))
+        .get("name".into(), vec![]);
+    let results = pipeline.execute().await;
+
+    assert_eq!(single(&results[0]), &ValueType::Int(2));
+    assert!(results[1].is_err());
+    assert_eq!(single(&results[2]), &ValueType::String("inferno".into()));
+}
+
+#[tokio::test]
+async fn test_pipeline_reads_own_writes() {
+    let mut client = connect().await;
+
+    let mut pipeline = client.pipeline();
+    for index in 0..100 {
+        pipeline
+            .set("key".into(), vec![], ValueType::Int(index))
+            .get("key".into(), vec![]);
+    }
+    let results = pipeline.execute().await;
+    assert_eq!(results.len(), 200);
+    for (index, get) in results.iter().skip(1).step_by(2).enumerate() {
+        assert_eq!(single(get), &ValueType::Int(index as i32));
+    }
+}
+
+#[tokio::test]
+async fn test_empty_pipeline() {
+    let mut client = connect().await;
+    assert!(client.pipeline().is_empty());
+    assert!(client.pipeline().execute().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_client_ref_many_tasks() {
+    let client = connect().await.into_ref();
+
+    let tasks: Vec<_> = (0..200)
+        .map(|index| {
+            let client = client.clone();
+            tokio::spawn(async move {
+                let key = Key::from(format!("key{}", index));
+                client
+                    .set(key.clone(), vec![], ValueType::Int(index))
+                    .await
+                    .unwrap();
+                client.get(key, vec![]).await
+            })
+        })
+        .collect();
+
+    for (index, task) in tasks.into_iter().enumerate() {
+        let value = task.await.unwrap();
+        assert_eq!(single(&value), &ValueType::Int(index as i32));
+    }
+}
+
+#[tokio::test]
+async fn test_client_ref_connection_closed() {
+    let (addr, mut connections) = serve().await;
+    let client = ClientRef::connect(&addr).await.unwrap();
+    client.incr("counter".into()).await.unwrap();
+
+    connections.recv().await.unwrap().abort();
+    let closed = client.incr("counter".into()).await;
+    assert!(matches!(
+        closed,
+        Err(errors::InfernoError::ConnectionClosed)
+    ));
+}
+
+type Connections = UnboundedReceiver<JoinHandle<errors::Result<()>>>;
+
+// accepts any number of connections, handing back each connection task
+async fn serve() -> (String, Connections) {
+    let (addr, connections, _accept) = serve_on("127.0.0.1:0").await;
+    (addr, connections)
+}
+
+async fn serve_on(addr: &str) -> (String, Connections, JoinHandle<()>) {
+    let listener = TcpListener::bind(addr).await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (connections, accepted) = mpsc::unbounded_channel();
+    let accept = tokio::spawn(async move {
+        let state = state::State::default();
+        loop {
+            let (stream, addr) = listener.accept().await.unwrap();
+            let connection =
+                connection::handle(state.clone(), stream, addr, frame::DEFAULT_MAX_FRAME_SIZE);
+            let _ = connections.send(connection);
+        }
+    });
+    (addr.to_string(), accepted, accept)
+}
+
+// closes the listener and every connection, losing all state
+async fn stop(accept: JoinHandle<()>, mut connections: Connections) {
+    accept.abort();
+    let _ = accept.await;
+    while let Ok(connection) = connections.try_recv() {
+        connection.abort();
+        let _ = connection.await;
+    }
+}
+
+fn options(min_size: usize, max_size: usize, idle_timeout: Duration) -> PoolOptions {
+    PoolOptions {
+        min_size,
+        max_size,
+        idle_timeout,
+        ..PoolOptions::default()
+    }
+}
+
+#[tokio::test]
+async fn test_pool_executor() {
+    let (addr, _connections) = serve().await;
+    let pool = Pool::connect_with(&addr, options(2, 4, Duration::from_secs(60)))
+        .await
+        .unwrap();
+    assert_eq!(pool.size(), 2);
+    assert_eq!(pool.idle(), 2);
+
+    pool.set("key".into(), vec![], ValueType::Int(1))
+        .await
+        .unwrap();
+    let value = pool.incr("key".into()).await;
+    assert_eq!(single(&value), &ValueType::Int(2));
+
+    // pipelines work on a checked out connection
+    let mut client = pool.get().await.unwrap();
+    let mut pipeline = client.pipeline();
+    pipeline.incr("key".into()).incr("key".into());
+    assert_eq!(pipeline.execute().await.len(), 2);
+    drop(client);
+    assert_eq!(pool.size(), 2);
+}
+
+#[tokio::test]
+async fn test_pool_waits_for_max_size() {
+    let (addr, _connections) = serve().await;
+    let pool = Pool::connect_with(&addr, options(0, 1, Duration::from_secs(60)))
+        .await
+        .unwrap();
+
+    let held = pool.get().await.unwrap();
+    let waiting = {
+        let pool = pool.clone();
+        tokio::spawn(async move { pool.incr("key".into()).await })
+    };
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!waiting.is_finished());
+    assert_eq!(pool.size(), 1);
+
+    drop(held);
+    let value = waiting.await.unwrap();
+    assert_eq!(single(&value), &ValueType::Int(1));
+    assert_eq!(pool.size(), 1);
+}
+
+#[tokio::test]
+async fn test_pool_idle_timeout() {
+    let (addr, _connections) = serve().await;
+    let pool = Pool::connect_with(&addr, options(1, 4, Duration::from_millis(50)))
+        .await
+        .unwrap();
+
+    let first = pool.get().await.unwrap();
+    let second = pool.get().await.unwrap();
+    let third = pool.get().await.unwrap();
+    drop((first, second, third));
+    assert_eq!(pool.idle(), 3);
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let client = pool.get().await.unwrap();
+    // expired connections are closed down to the minimum
+    assert_eq!(pool.size(), 1);
+    drop(client);
+    assert_eq!(pool.idle(), 1);
+}
+
+#[tokio::test]
+async fn test_pool_keeps_min_size_when_pruning() {
+    let (addr, _connections) = serve().await;
+    let pool = Pool::connect_with(&addr, options(2, 4, Duration::from_millis(50)))
+        .await
+        .unwrap();
+
+    let first = pool.get().await.unwrap();
+    let second = pool.get().await.unwrap();
+    drop(first);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    // the expired connection is still needed to stay at the minimum
+    drop(second);
+    assert_eq!(pool.idle(), 2);
+}
+
+#[tokio::test]
+async fn test_pool_refills_to_min_size() {
+    let (addr, mut connections) = serve().await;
+    let pool = Pool::connect_with(&addr, options(2, 4, Duration::from_secs(60)))
+        .await
+        .unwrap();
+    for _ in 0..2 {
+        connections.recv().await.unwrap().abort();
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let value = pool.incr("key".into()).await;
+    assert_eq!(single(&value), &ValueType::Int(1));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(pool.size(), 2);
+    assert_eq!(pool.idle(), 2);
+}
+
+// completes the handshake on the first `count` connections, then never answers nor closes
+async fn silent(count: usize) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let state = state::State::default();
+        let mut held = Vec::new();
+        for accepted in 0.. {
+            let (mut stream, addr) = listener.accept().await.unwrap();
+            if accepted >= count {
+                connection::handle(state.clone(), stream, addr, frame::DEFAULT_MAX_FRAME_SIZE);
+                continue;
+            }
+            let max = frame::DEFAULT_MAX_FRAME_SIZE;
+            frame::read_packet::<Hello, _>(&mut stream, max)
+                .await
+                .unwrap();
+            frame::write_packet(&mut stream, &Hello::default())
+                .await
+                .unwrap();
+            held.push(stream);
+        }
+    });
+    addr.to_string()
+}
+
+#[tokio::test]
+async fn test_cancelled_write_reconnects() {
+    let addr = silent(1).await;
+    let mut client = Client::connect(&addr).await.unwrap();
+
+    // far more than the socket buffers hold, so the write stalls part way through a frame
+    let value = ValueType::String("x".repeat(1024));
+    let mut pipeline = client.pipeline();
+    for _ in 0..16 * 1024 {
+        pipeline.set("key".into(), vec![], value.clone());
+    }
+    let sent = tokio::time::timeout(Duration::from_millis(100), pipeline.execute()).await;
+    assert!(sent.is_err());
+    assert!(client.is_closed());
+
+    let value = client.incr("key".into()).await;
+    assert_eq!(single(&value), &ValueType::Int(1));
+}
+
+#[tokio::test]
+async fn test_pool_replaces_unresponsive_connection() {
+    let addr = silent(1).await;
+    let mut options = options(1, 1, Duration::from_secs(60));
+    options.check_timeout = Duration::from_millis(50);
+    let pool = Pool::connect_with(&addr, options).await.unwrap();
+
+    let value = pool.incr("key".into()).await;
+    assert_eq!(single(&value), &ValueType::Int(1));
+    assert_eq!(pool.size(), 1);
+}
+
+#[tokio::test]
+async fn test_pool_replaces_closed_connection() {
+    let (addr, mut connections) = serve().await;
+    let pool = Pool::connect_with(&addr, options(1, 1, Duration::from_secs(60)))
+        .await
+        .unwrap();
+    pool.incr("key".into()).await.unwrap();
+
+    connections.recv().await.unwrap().abort();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let value = pool.incr("key".into()).await;
+    assert_eq!(single(&value), &ValueType::Int(2));
+    assert_eq!(pool.size(), 1);
+}
+
+fn recording(policy: ReconnectPolicy) -> (ReconnectPolicy, Arc<Mutex<Vec<String>>>) {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorded = events.clone();
+    let policy = policy.on_event(move |event| {
+        let name = match event {
+            ReconnectEvent::Disconnected => "disconnected",
+            ReconnectEvent::Reconnecting { .. } => "reconnecting",
+            ReconnectEvent::Reconnected { .. } => "reconnected",
+            ReconnectEvent::Failed { .. } => "failed",
+        };
+        recorded.lock().unwrap().push(name.to_string());
+    });
+    (policy, events)
+}
+
+fn fast_reconnect(max_attempts: u32) -> ReconnectPolicy {
+    let mut policy = ReconnectPolicy::default();
+    policy.backoff = Backoff {
+        initial: Duration::from_millis(10),
+        max: Duration::from_millis(40),
+    };
+    policy.max_attempts = max_attempts;
+    policy
+}
+
+#[tokio::test]
+async fn test_reconnect_after_restart() {
+    let (addr, connections, accept) = serve_on("127.0.0.1:0").await;
+    let (policy, events) = recording(fast_reconnect(5));
+    let mut client = Client::connect(&addr).await.unwrap().with_reconnect(policy);
+    client
+        .set("key".into(), vec![], ValueType::Int(1))
+        .await
+        .unwrap();
+
+    stop(accept, connections).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(client.is_closed());
+    let (_, _connections, _accept) = serve_on(&addr).await;
+
+    // the restarted server lost its state
+    let value = client.get("key".into(), vec![]).await;
+    assert_eq!(single(&value), &ValueType::None);
+    let value = client.incr("counter".into()).await;
+    assert_eq!(single(&value), &ValueType::Int(1));
+    assert_eq!(
+        *events.lock().unwrap(),
+        ["disconnected", "reconnecting", "reconnected"]
+    );
+}
+
+#[tokio::test]
+async fn test_reconnect_gives_up() {
+    let (addr, connections, accept) = serve_on("127.0.0.1:0").await;
+    let (policy, events) = recording(fast_reconnect(3));
+    let mut client = Client::connect(&addr).await.unwrap().with_reconnect(policy);
+
+    stop(accept, connections).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let failed = client.get("key".into(), vec![]).await;
+    assert!(matches!(failed, Err(InfernoError::Io(_))));
+    let events = events.lock().unwrap();
+    assert_eq!(events.first().unwrap(), "disconnected");
+    assert_eq!(
+        events.iter().filter(|name| *name == "reconnecting").count(),
+        3
+    );
+    assert_eq!(events.last().unwrap(), "failed");
+}
+
+#[tokio::test]
+async fn test_reconnect_disabled() {
+    let (addr, connections, accept) = serve_on("127.0.0.1:0").await;
+    let mut client = Client::connect(&addr)
+        .await
+        .unwrap()
+        .with_reconnect(ReconnectPolicy::disabled());
+
+    stop(accept, connections).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let (_, _connections, _accept) = serve_on(&addr).await;
+
+    let failed = client.get("key".into(), vec![]).await;
+    assert!(matches!(failed, Err(InfernoError::ConnectionClosed)));
+}
+
+// drops the first `drops` connections after reading one command, without answering it
+async fn flaky(drops: usize) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let state = state::State::default();
+        for accepted in 0.. {
+            let (mut stream, addr) = listener.accept().await.unwrap();
+            if accepted >= drops {
+                connection::handle(state.clone(), stream, addr, frame::DEFAULT_MAX_FRAME_SIZE);
+                continue;
+            }
+            let max = frame::DEFAULT_MAX_FRAME_SIZE;
+            frame::read_packet::<Hello, _>(&mut stream, max)
+                .await
+                .unwrap();
+            frame::write_packet(&mut stream, &Hello::default())
+                .await
+                .unwrap();
+            frame::read_packet::<Envelope<ClientCommand>, _>(&mut stream, max)
+                .await
+                .unwrap();
+        }
+    });
+    addr.to_string()
+}
+
+#[tokio::test]
+async fn test_retry_idempotent_only() {
+    let addr = flaky(2).await;
+    let mut client = Client::connect(&addr)
+        .await
+        .unwrap()
+        .with_reconnect(fast_reconnect(5));
+
+    let failed = client.incr("counter".into()).await;
+    assert!(matches!(failed, Err(InfernoError::ConnectionClosed)));
+
+    // the second connection is dropped too, the third answers
+    let value = client.get("counter".into(), vec![]).await;
+    assert_eq!(single(&value), &ValueType::None);
+}
+
+#[tokio::test]
+async fn test_retry_opt_in() {
+    let addr = flaky(1).await;
+    let mut client = Client::connect(&addr)
+        .await
+        .unwrap()
+        .with_reconnect(fast_reconnect(5));
+
+    let value = client
+        .retry(RetryPolicy::Always)
+        .incr("counter".into())
+        .await;
+    assert_eq!(single(&value), &ValueType::Int(1));
+
+    let mut client = Client::connect(&flaky(1).await)
+        .await
+        .unwrap()
+        .with_reconnect(fast_reconnect(5));
+    let failed = client
+        .retry(RetryPolicy::Never)
+        .get("key".into(), vec![])
+        .await;
+    assert!(matches!(failed, Err(InfernoError::ConnectionClosed)));
+}
+
+#[test]
+fn test_retry_policy() {
+    let get = ClientCommand::Get {
+        key: "key".into(),
+        path: vec![],
+    };
+    let incr = ClientCommand::Incr { key: "key".into() };
+    let push = ClientCommand::LLPush {
+        key: "key".into(),
+        value: ValueType::Int(1),
+    };
+    assert!(RetryPolicy::Idempotent.allows(&get));
+    assert!(!RetryPolicy::Idempotent.allows(&incr));
+    assert!(!RetryPolicy::Idempotent.allows(&push));
+    assert!(RetryPolicy::Always.allows(&push));
+    assert!(!RetryPolicy::Never.allows(&get));
+
+    let del = |path| ClientCommand::Del {
+        key: "key".into(),
+        path,
+    };
+    assert!(RetryPolicy::Idempotent.allows(&del(vec![])));
+    assert!(RetryPolicy::Idempotent.allows(&del(vec![Instruction::Hash("field".into())])));
+    assert!(!RetryPolicy::Idempotent.allows(&del(vec![Instruction::Index(0)])));
+    assert!(RetryPolicy::Idempotent.allows(&del(vec![
+        Instruction::Index(0),
+        Instruction::Hash("field".into()),
+    ])));
+}
+
+#[test]
+fn test_backoff() {
+    let backoff = Backoff {
+        initial: Duration::from_millis(10),
+        max: Duration::from_millis(50),
+    };
+    assert_eq!(backoff.delay(1), Duration::from_millis(10));
+    assert_eq!(backoff.delay(3), Duration::from_millis(40));
+    assert_eq!(backoff.delay(4), Duration::from_millis(50));
+    assert_eq!(backoff.delay(100), Duration::from_millis(50));
+}
+
+#[tokio::test]
+async fn test_round_trips_not_delayed() {
+    // a frame split over two writes waits on delayed acks, about 40ms a command
+    let mut client = connect().await;
+    let start = std::time::Instant::now();
+    for _ in 0..100 {
+        client.incr("key".into()).await.unwrap();
+    }
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
@@ -0,0 +1,384 @@
+use packets::value::ValueType;
+use packets::{ClientCommandExecutor, ServerResponse};
+use server::expiry::{self, ManualClock};
+use server::state;
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_ttl_missing() {
+    let state = state::State::default();
+    let ttl_result = state.ttl("test".into()).await;
+    assert!(matches!(
+        ttl_result,
+        Ok(ServerResponse::OptInt { value: None })
+    ));
+}
+
+#[tokio::test]
+async fn test_ttl_persistent() {
+    let state = state::State::default();
+    state
+        .set("test".into(), vec![], ValueType::Int(1))
+        .await
+        .unwrap();
+    let ttl_result = state.ttl("test".into()).await;
+    assert!(matches!(
+        ttl_result,
+        Ok(ServerResponse::OptInt { value: None })
+    ));
+}
+
+#[tokio::test]
+async fn test_expire_lazy() {
+    let clock = ManualClock::default();
+    let state = state::State::with_clock(clock.clone());
+    state
+        .set("test".into(), vec![], ValueType::Int(1))
+        .await
+        .unwrap();
+
+    let expire_result = state.expire("test".into(), 10).await;
+    assert!(matches!(
+        expire_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1)
+        })
+    ));
+
+    clock.advance(Duration::from_secs(4));
+    let ttl_result = state.ttl("test".into()).await;
+    assert!(matches!(
+        ttl_result,
+        Ok(ServerResponse::OptInt { value: Some(6) })
+    ));
+
+    clock.advance(Duration::from_secs(6));
+    let get_result = state.get("test".into(), vec![]).await;
+    assert!(matches!(
+        get_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::None
+        })
+    ));
+    assert!(state.is_empty());
+}
+
+#[tokio::test]
+async fn test_expire_missing() {
+    let state = state::State::default();
+    let expire_result = state.expire("test".into(), 10).await;
+    assert!(matches!(
+        expire_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(0)
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_persist() {
+    let clock = ManualClock::default();
+    let state = state::State::with_clock(clock.clone());
+    state
+        .set_ex("test".into(), ValueType::Int(1), 10)
+        .await
+        .unwrap();
+
+    let persist_result = state.persist("test".into()).await;
+    assert!(matches!(
+        persist_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1)
+        })
+    ));
+
+    clock.advance(Duration::from_secs(20));
+    let get_result = state.get("test".into(), vec![]).await;
+    assert!(matches!(
+        get_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1)
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_set_clears_expiry() {
+    let clock = ManualClock::default();
+    let state = state::State::with_clock(clock.clone());
+    state
+        .set_ex("test".into(), ValueType::Int(1), 10)
+        .await
+        .unwrap();
+    state
+        .set("test".into(), vec![], ValueType::Int(2))
+        .await
+        .unwrap();
+
+    clock.advance(Duration::from_secs(20));
+    let get_result = state.get("test".into(), vec![]).await;
+    assert!(matches!(
+        get_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(2)
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_get_ex() {
+    let clock = ManualClock::default();
+    let state = state::State::with_clock(clock.clone());
+    state
+        .set("test".into(), vec![], ValueType::Int(1))
+        .await
+        .unwrap();
+
+    let get_ex_result = state.get_ex("test".into(), 5).await;
+    assert!(matches!(
+        get_ex_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1)
+        })
+    ));
+
+    clock.advance(Duration::from_secs(5));
+    let get_result = state.get("test".into(), vec![]).await;
+    assert!(matches!(
+        get_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::None
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_sweep() {
+    let clock = ManualClock::default();
+    let state = state::State::with_clock(clock.clone());
+    state
+        .set_ex("short".into(), ValueType::Int(1), 5)
+        .await
+        .unwrap();
+    state
+        .set_ex("long".into(), ValueType::Int(1), 15)
+        .await
+        .unwrap();
+    state
+        .set("forever".into(), vec![], ValueType::Int(1))
+        .await
+        .unwrap();
+
+    clock.advance(Duration::from_secs(10));
+    assert_eq!(state.sweep(), 1);
+    assert_eq!(state.len(), 2);
+
+    clock.advance(Duration::from_secs(10));
+    assert_eq!(state.sweep(), 1);
+    assert_eq!(state.len(), 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_sweeper_task() {
+    let clock = ManualClock::default();
+    let state = state::State::with_clock(clock.clone());
+    state
+        .set_ex("test".into(), ValueType::Int(1), 5)
+        .await
+        .unwrap();
+
+    let sweeper = expiry::spawn_sweeper(state.clone(), Duration::from_secs(1));
+
+    clock.advance(Duration::from_secs(5));
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    assert!(state.is_empty());
+
+    sweeper.abort();
+}
+
+#[tokio::test]
+async fn test_hset_ex_reclaimed() {
+    let clock = ManualClock::default();
+    let state = state::State::with_clock(clock.clone());
+    let hset_ex_result = state
+        .hset_ex("test".into(), "field".into(), ValueType::Int(1), 5)
+        .await;
+    assert!(matches!(
+        hset_ex_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1)
+        })
+    ));
+
+    clock.advance(Duration::from_secs(4));
+    assert_eq!(state.sweep(), 0);
+    assert_eq!(state.len(), 1);
+
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(state.sweep(), 1);
+    assert!(state.is_empty());
+}
+
+#[tokio::test]
+async fn test_hexpire_missing_field() {
+    let state = state::State::default();
+    let hexpire_result = state.hexpire("test".into(), "field".into(), 5).await;
+    assert!(matches!(
+        hexpire_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(0)
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_sadd_ex_refresh() {
+    let clock = ManualClock::default();
+    let state = state::State::with_clock(clock.clone());
+    let first = state.sadd_ex("test".into(), "member".into(), 5).await;
+    assert!(matches!(
+        first,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1)
+        })
+    ));
+
+    let second = state.sadd_ex("test".into(), "member".into(), 10).await;
+    assert!(matches!(
+        second,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(0)
+        })
+    ));
+
+    clock.advance(Duration::from_secs(5));
+    let sexpire_result = state.sexpire("test".into(), "member".into(), 1).await;
+    assert!(matches!(
+        sexpire_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1)
+        })
+    ));
+
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(state.sweep(), 1);
+    assert!(state.is_empty());
+}
+
+#[tokio::test]
+async fn test_list_member_expiry() {
+    let clock = ManualClock::default();
+    let state = state::State::with_clock(clock.clone());
+    state
+        .lrpush_ex("test".into(), ValueType::Int(1), 5)
+        .await
+        .unwrap();
+    state
+        .lrpush_ex("test".into(), ValueType::Int(2), 15)
+        .await
+        .unwrap();
+
+    clock.advance(Duration::from_secs(10));
+    assert_eq!(state.sweep(), 0);
+    assert_eq!(state.len(), 1);
+
+    let lexpire_result = state.lexpire("test".into(), 0, 1).await;
+    assert!(matches!(
+        lexpire_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1)
+        })
+    ));
+
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(state.sweep(), 1);
+    assert!(state.is_empty());
+}
+
+#[tokio::test]
+async fn test_hash_field_expires_on_read() {
+    let clock = ManualClock::default();
+    let state = state::State::with_clock(clock.clone());
+    state
+        .hset("test".into(), "kept".into(), ValueType::Int(1))
+        .await
+        .unwrap();
+    state
+        .hset_ex("test".into(), "field".into(), ValueType::Int(2), 5)
+        .await
+        .unwrap();
+
+    clock.advance(Duration::from_secs(5));
+    let hget_result = state.hget("test".into(), "field".into()).await;
+    assert!(matches!(
+        hget_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::None
+        })
+    ));
+
+    let hlen_result = state.hlen("test".into()).await;
+    assert!(matches!(
+        hlen_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1)
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_emptied_hash_removed_on_read() {
+    let clock = ManualClock::default();
+    let state = state::State::with_clock(clock.clone());
+    state
+        .hset_ex("test".into(), "field".into(), ValueType::Int(1), 1)
+        .await
+        .unwrap();
+
+    clock.advance(Duration::from_secs(2));
+    let get_result = state.get("test".into(), vec![]).await;
+    assert!(matches!(
+        get_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::None
+        })
+    ));
+    assert!(state.is_empty());
+
+    let set_nx_result = state.set_nx("test".into(), ValueType::Int(2)).await;
+    assert!(matches!(
+        set_nx_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1)
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_emptied_list_removed_on_read() {
+    let clock = ManualClock::default();
+    let state = state::State::with_clock(clock.clone());
+    state
+        .lrpush_ex("test".into(), ValueType::Int(1), 1)
+        .await
+        .unwrap();
+    state
+        .lrpush_ex("test".into(), ValueType::Int(2), 2)
+        .await
+        .unwrap();
+
+    clock.advance(Duration::from_secs(1));
+    let lrange_result = state.lrange("test".into(), 0, 10).await;
+    assert!(matches!(
+        lrange_result,
+        Ok(ServerResponse::Bulk { values }) if values == vec![ValueType::Int(2)]
+    ));
+
+    clock.advance(Duration::from_secs(1));
+    let lrange_result = state.lrange("test".into(), 0, 10).await;
+    assert!(matches!(
+        lrange_result,
+        Ok(ServerResponse::Bulk { values }) if values.is_empty()
+    ));
+    assert!(state.is_empty());
+}
@@ -0,0 +1,331 @@
+use errors::{InfernoError, StateError};
+use packets::value::ValueType;
+use packets::{ClientCommandExecutor, ServerResponse};
+use server::state;
+
+fn bulk(response: errors::Result<ServerResponse>) -> Vec<ValueType> {
+    match response {
+        Ok(ServerResponse::Bulk { values }) => values,
+        other => panic!("Expected bulk response, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_hset_hget() {
+    let state = state::State::default();
+    let first = state
+        .hset("test".into(), "field".into(), ValueType::Int(1))
+        .await;
+    assert!(matches!(
+        first,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1)
+        })
+    ));
+
+    let second = state
+        .hset("test".into(), "field".into(), ValueType::Int(2))
+        .await;
+    assert!(matches!(
+        second,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(0)
+        })
+    ));
+
+    let hget_result = state.hget("test".into(), "field".into()).await;
+    assert!(matches!(
+        hget_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(2)
+        })
+    ));
+
+    let missing = state.hget("test".into(), "missing".into()).await;
+    assert!(matches!(
+        missing,
+        Ok(ServerResponse::Single {
+            value: ValueType::None
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_hset_nx() {
+    let state = state::State::default();
+    state
+        .hset_nx("test".into(), "field".into(), ValueType::Int(1))
+        .await
+        .unwrap();
+    let second = state
+        .hset_nx("test".into(), "field".into(), ValueType::Int(2))
+        .await;
+    assert!(matches!(
+        second,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(0)
+        })
+    ));
+
+    let hget_result = state.hget("test".into(), "field".into()).await;
+    assert!(matches!(
+        hget_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1)
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_hmset_reads() {
+    let state = state::State::default();
+    state
+        .hmset(
+            "test".into(),
+            vec![
+                ("a".into(), ValueType::Int(1)),
+                ("b".into(), ValueType::Int(2)),
+            ],
+        )
+        .await
+        .unwrap();
+
+    let hlen_result = state.hlen("test".into()).await;
+    assert!(matches!(
+        hlen_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(2)
+        })
+    ));
+
+    let values = bulk(
+        state
+            .hmget("test".into(), vec!["a".into(), "missing".into()])
+            .await,
+    );
+    assert_eq!(values, vec![ValueType::Int(1), ValueType::None]);
+
+    let mut keys = bulk(state.hkeys("test".into()).await);
+    keys.sort_by_key(|key| format!("{:?}", key));
+    assert_eq!(
+        keys,
+        vec![ValueType::String("a".into()), ValueType::String("b".into())]
+    );
+
+    let mut values = bulk(state.hvalues("test".into()).await);
+    values.sort_by_key(|value| format!("{:?}", value));
+    assert_eq!(values, vec![ValueType::Int(1), ValueType::Int(2)]);
+
+    let all = bulk(state.hget_all("test".into()).await);
+    assert_eq!(all.len(), 4);
+
+    let exists = state.hexists("test".into(), "a".into()).await;
+    assert!(matches!(
+        exists,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1)
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_hmset_nx_all_or_nothing() {
+    let state = state::State::default();
+    state
+        .hset("test".into(), "b".into(), ValueType::Int(0))
+        .await
+        .unwrap();
+
+    let hmset_nx_result = state
+        .hmset_nx(
+            "test".into(),
+            vec![
+                ("a".into(), ValueType::Int(1)),
+                ("b".into(), ValueType::Int(2)),
+            ],
+        )
+        .await;
+    assert!(matches!(
+        hmset_nx_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(0)
+        })
+    ));
+
+    let values = bulk(
+        state
+            .hmget("test".into(), vec!["a".into(), "b".into()])
+            .await,
+    );
+    assert_eq!(values, vec![ValueType::None, ValueType::Int(0)]);
+}
+
+#[tokio::test]
+async fn test_hdel_removes_empty() {
+    let state = state::State::default();
+    state
+        .hmset(
+            "test".into(),
+            vec![
+                ("a".into(), ValueType::Int(1)),
+                ("b".into(), ValueType::Int(2)),
+            ],
+        )
+        .await
+        .unwrap();
+
+    let hdel_result = state
+        .hdel("test".into(), vec!["a".into(), "missing".into()])
+        .await;
+    assert!(matches!(
+        hdel_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1)
+        })
+    ));
+    assert_eq!(state.len(), 1);
+
+    let values = bulk(state.hdel_get("test".into(), vec!["b".into()]).await);
+    assert_eq!(values, vec![ValueType::Int(2)]);
+    assert!(state.is_empty());
+}
+
+#[tokio::test]
+async fn test_hpop_rand() {
+    let state = state::State::default();
+    state
+        .hmset(
+            "test".into(),
+            vec![
+                ("a".into(), ValueType::Int(1)),
+                ("b".into(), ValueType::Int(2)),
+                ("c".into(), ValueType::Int(3)),
+            ],
+        )
+        .await
+        .unwrap();
+
+    let popped = bulk(state.hpop_rand("test".into(), 2).await);
+    assert_eq!(popped.len(), 4);
+
+    let remaining = bulk(state.hpop_rand("test".into(), 2).await);
+    assert_eq!(remaining.len(), 2);
+    assert!(state.is_empty());
+}
+
+#[tokio::test]
+async fn test_hincr_by() {
+    let state = state::State::default();
+    let hincr_result = state.hincr_by("test".into(), "field".into(), 5).await;
+    assert!(matches!(
+        hincr_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(5)
+        })
+    ));
+
+    let hdecr_result = state.hdecr("test".into(), "field".into()).await;
+    assert!(matches!(
+        hdecr_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(4)
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_hincr_bad_type() {
+    let state = state::State::default();
+    state
+        .hset("test".into(), "field".into(), ValueType::String("a".into()))
+        .await
+        .unwrap();
+    let hincr_result = state.hincr("test".into(), "field".into()).await;
+    assert!(matches!(
+        hincr_result,
+        Err(InfernoError::State(StateError::BadKeyType))
+    ));
+}
+
+#[tokio::test]
+async fn test_hincr_overflow() {
+    let state = state::State::default();
+    state
+        .hincr_by("test".into(), "field".into(), i32::MAX.into())
+        .await
+        .unwrap();
+    let hincr_result = state.hincr("test".into(), "field".into()).await;
+    assert!(matches!(
+        hincr_result,
+        Err(InfernoError::State(StateError::Overflow))
+    ));
+}
+
+#[tokio::test]
+async fn test_hincr_overflow_creates_nothing() {
+    let state = state::State::default();
+    let hincr_result = state
+        .hincr_by("test".into(), "field".into(), i64::MAX)
+        .await;
+    assert!(matches!(
+        hincr_result,
+        Err(InfernoError::State(StateError::Overflow))
+    ));
+    let get_result = state.get("test".into(), vec![]).await.unwrap();
+    assert!(matches!(
+        get_result,
+        ServerResponse::Single {
+            value: ValueType::None
+        }
+    ));
+
+    state
+        .hset("test".into(), "other".into(), ValueType::Int(1))
+        .await
+        .unwrap();
+    let hincr_result = state
+        .hincr_by("test".into(), "field".into(), i64::MAX)
+        .await;
+    assert!(hincr_result.is_err());
+    let hexists_result = state.hexists("test".into(), "field".into()).await.unwrap();
+    assert!(matches!(
+        hexists_result,
+        ServerResponse::Single {
+            value: ValueType::Int(0)
+        }
+    ));
+}
+
+#[tokio::test]
+async fn test_hash_on_value_key() {
+    let state = state::State::default();
+    state
+        .set("test".into(), vec![], ValueType::Int(1))
+        .await
+        .unwrap();
+    let hget_result = state.hget("test".into(), "field".into()).await;
+    assert!(matches!(
+        hget_result,
+        Err(InfernoError::State(StateError::BadKeyType))
+    ));
+}
+
+#[tokio::test]
+async fn test_hdecr_by_signed() {
+    let state = state::State::default();
+    let hdecr_result = state.hdecr_by("test".into(), "field".into(), 3).await;
+    assert!(matches!(
+        hdecr_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(-3)
+        })
+    ));
+
+    let hdecr_result = state.hdecr_by("test".into(), "field".into(), -5).await;
+    assert!(matches!(
+        hdecr_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(2)
+        })
+    ));
+}
@@ -0,0 +1,152 @@
+use packets::value::ValueType;
+use packets::{ClientCommandExecutor, ServerResponse};
+use server::expiry::ManualClock;
+use server::state;
+use std::time::Duration;
+
+fn bulk(response: errors::Result<ServerResponse>) -> Vec<ValueType> {
+    match response {
+        Ok(ServerResponse::Bulk { values }) => values,
+        other => panic!("Expected bulk response, got {:?}", other),
+    }
+}
+
+fn ints(values: &[i32]) -> Vec<ValueType> {
+    values.iter().map(|value| ValueType::Int(*value)).collect()
+}
+
+async fn with_items() -> state::State {
+    let state = state::State::default();
+    for value in 1..=3 {
+        state
+            .clone()
+            .lrpush("test".into(), ValueType::Int(value))
+            .await
+            .unwrap();
+    }
+    state
+        .clone()
+        .llpush("test".into(), ValueType::Int(0))
+        .await
+        .unwrap();
+    state
+}
+
+#[tokio::test]
+async fn test_push_lrange() {
+    let state = with_items().await;
+    let all = bulk(state.clone().lrange("test".into(), 0, 10).await);
+    assert_eq!(all, ints(&[0, 1, 2, 3]));
+
+    let middle = bulk(state.clone().lrange("test".into(), 1, 2).await);
+    assert_eq!(middle, ints(&[1, 2]));
+
+    let reversed = bulk(state.clone().lrange("test".into(), 2, 1).await);
+    assert!(reversed.is_empty());
+
+    let missing = bulk(state.lrange("missing".into(), 0, 10).await);
+    assert!(missing.is_empty());
+}
+
+#[tokio::test]
+async fn test_pop_count() {
+    let state = with_items().await;
+    let front = bulk(state.clone().llpop("test".into(), 2).await);
+    assert_eq!(front, ints(&[0, 1]));
+
+    let back = bulk(state.clone().lrpop("test".into(), 1).await);
+    assert_eq!(back, ints(&[3]));
+
+    let rest = bulk(state.clone().lrpop("test".into(), 5).await);
+    assert_eq!(rest, ints(&[2]));
+
+    let empty = bulk(state.llpop("test".into(), 1).await);
+    assert!(empty.is_empty());
+}
+
+#[tokio::test]
+async fn test_empty_list_removed() {
+    let state = with_items().await;
+    state.clone().llpop("test".into(), 4).await.unwrap();
+
+    // the key is free again, so a plain value can take its place
+    let removed = state.clone().get("test".into(), vec![]).await;
+    assert!(matches!(
+        removed,
+        Ok(ServerResponse::Single {
+            value: ValueType::None
+        })
+    ));
+    let created = state.llpush_nx("test".into(), ValueType::Int(1)).await;
+    assert!(matches!(
+        created,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1)
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_push_nx() {
+    let state = state::State::default();
+    let created = state
+        .clone()
+        .lrpush_nx("test".into(), ValueType::Int(1))
+        .await;
+    assert!(matches!(
+        created,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1)
+        })
+    ));
+
+    let existing = state
+        .clone()
+        .llpush_nx("test".into(), ValueType::Int(2))
+        .await;
+    assert!(matches!(
+        existing,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(0)
+        })
+    ));
+
+    let all = bulk(state.lrange("test".into(), 0, 10).await);
+    assert_eq!(all, ints(&[1]));
+}
+
+#[tokio::test]
+async fn test_list_wrong_type() {
+    let state = state::State::default();
+    state
+        .clone()
+        .set("test".into(), vec![], ValueType::Int(1))
+        .await
+        .unwrap();
+    assert!(state
+        .clone()
+        .lrpush("test".into(), ValueType::Int(1))
+        .await
+        .is_err());
+    assert!(state.llpop("test".into(), 1).await.is_err());
+}
+
+#[tokio::test]
+async fn test_pop_skips_expired() {
+    let clock = ManualClock::default();
+    let state = state::State::with_clock(clock.clone());
+    state
+        .clone()
+        .lrpush_ex("test".into(), ValueType::Int(1), 5)
+        .await
+        .unwrap();
+    state
+        .clone()
+        .lrpush("test".into(), ValueType::Int(2))
+        .await
+        .unwrap();
+    clock.advance(Duration::from_secs(5));
+
+    let popped = bulk(state.clone().llpop("test".into(), 2).await);
+    assert_eq!(popped, ints(&[2]));
+}
@@ -0,0 +1,247 @@
+use errors::{InfernoError, PacketsError, StateError};
+use packets::frame::MAX_NESTING_DEPTH;
+use packets::instruction::Instruction;
+use packets::value::ValueType;
+use packets::{ClientCommandExecutor, Packet, ServerResponse};
+use server::state;
+
+fn single(response: errors::Result<ServerResponse>) -> ValueType {
+    match response {
+        Ok(ServerResponse::Single { value }) => value,
+        other => panic!("Expected single response, got {:?}", other),
+    }
+}
+
+fn session() -> ValueType {
+    ValueType::Map(vec![
+        ("user".into(), ValueType::String("ada".into())),
+        (
+            "roles".into(),
+            ValueType::List(vec![
+                ValueType::String("admin".into()),
+                ValueType::Map(vec![("scope".into(), ValueType::Int(2))]),
+            ]),
+        ),
+    ])
+}
+
+fn sorted(value: ValueType) -> ValueType {
+    match value {
+        ValueType::Map(mut fields) => {
+            fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+            ValueType::Map(
+                fields
+                    .into_iter()
+                    .map(|(field, value)| (field, sorted(value)))
+                    .collect(),
+            )
+        }
+        ValueType::List(values) => ValueType::List(values.into_iter().map(sorted).collect()),
+        value => value,
+    }
+}
+
+#[tokio::test]
+async fn test_subtree_round_trip() {
+    let state = state::State::default();
+    state.set("test".into(), vec![], session()).await.unwrap();
+
+    let value = state.get("test".into(), vec![]).await;
+    assert_eq!(sorted(single(value)), sorted(session()));
+
+    // the stored subtree is a live hash
+    let user = state.hget("test".into(), "user".into()).await;
+    assert_eq!(single(user), ValueType::String("ada".into()));
+}
+
+#[tokio::test]
+async fn test_nested_paths() {
+    let state = state::State::default();
+    state.set("test".into(), vec![], session()).await.unwrap();
+    let scope = || {
+        vec![
+            Instruction::hash("roles"),
+            Instruction::index(1),
+            Instruction::hash("scope"),
+        ]
+    };
+
+    let value = state.get("test".into(), scope()).await;
+    assert_eq!(single(value), ValueType::Int(2));
+
+    state
+        .set("test".into(), scope(), ValueType::Int(3))
+        .await
+        .unwrap();
+    let value = state.get("test".into(), scope()).await;
+    assert_eq!(single(value), ValueType::Int(3));
+
+    let removed = state.del("test".into(), scope()).await;
+    assert_eq!(single(removed), ValueType::Int(1));
+    let value = state
+        .get(
+            "test".into(),
+            vec![Instruction::hash("roles"), Instruction::index(1)],
+        )
+        .await;
+    assert_eq!(single(value), ValueType::Map(vec![]));
+}
+
+#[tokio::test]
+async fn test_set_creates_nested_hashes() {
+    let state = state::State::default();
+    let path = || vec![Instruction::hash("hash-key"), Instruction::hash("nested")];
+    state
+        .set("test".into(), path(), ValueType::Int(1))
+        .await
+        .unwrap();
+
+    let value = state.get("test".into(), path()).await;
+    assert_eq!(single(value), ValueType::Int(1));
+
+    let missing_index = state
+        .set(
+            "test".into(),
+            vec![Instruction::hash("list"), Instruction::index(0)],
+            ValueType::Int(1),
+        )
+        .await;
+    assert!(matches!(
+        missing_index,
+        Err(InfernoError::State(StateError::IndexOutOfRange))
+    ));
+    let untouched = state
+        .get("test".into(), vec![Instruction::hash("list")])
+        .await;
+    assert_eq!(single(untouched), ValueType::None);
+}
+
+#[tokio::test]
+async fn test_list_holds_subtrees() {
+    let state = state::State::default();
+    state.lrpush("test".into(), session()).await.unwrap();
+    let Ok(ServerResponse::Bulk { values }) = state.llpop("test".into(), 1).await else {
+        panic!("Expected bulk response");
+    };
+    assert_eq!(
+        values.into_iter().map(sorted).collect::<Vec<_>>(),
+        vec![sorted(session())]
+    );
+}
+
+#[tokio::test]
+async fn test_nested_value_not_incrementable() {
+    let state = state::State::default();
+    state
+        .hset(
+            "test".into(),
+            "field".into(),
+            ValueType::List(vec![ValueType::Int(1)]),
+        )
+        .await
+        .unwrap();
+    let incr = state.hincr("test".into(), "field".into()).await;
+    assert!(matches!(
+        incr,
+        Err(InfernoError::State(StateError::BadKeyType))
+    ));
+}
+
+#[tokio::test]
+async fn test_wire_round_trip() {
+    let mut buffer = Vec::new();
+    session().write(&mut buffer).await.unwrap();
+    let read = ValueType::read(&mut buffer.as_slice()).await.unwrap();
+    assert_eq!(read, session());
+}
+
+fn nested(depth: usize) -> ValueType {
+    (0..depth).fold(ValueType::Int(1), |value, _| ValueType::List(vec![value]))
+}
+
+#[tokio::test]
+async fn test_deep_wire_value_rejected() {
+    // every level is a list tag and a length of one, rejected long before the stack runs out
+    let mut buffer = Vec::new();
+    for _ in 0..200_000 {
+        buffer.push(3);
+        buffer.extend_from_slice(&1u32.to_be_bytes());
+    }
+    let read = ValueType::read(&mut buffer.as_slice()).await;
+    assert!(matches!(
+        read,
+        Err(InfernoError::Packets(PacketsError::NestingTooDeep { limit }))
+            if limit == MAX_NESTING_DEPTH
+    ));
+
+    let mut buffer = Vec::new();
+    nested(MAX_NESTING_DEPTH).write(&mut buffer).await.unwrap();
+    let read = ValueType::read(&mut buffer.as_slice()).await.unwrap();
+    assert_eq!(read, nested(MAX_NESTING_DEPTH));
+}
+
+#[tokio::test]
+async fn test_deep_value_not_stored() {
+    let state = state::State::default();
+    let set = state
+        .set("test".into(), vec![], nested(MAX_NESTING_DEPTH + 1))
+        .await;
+    assert!(matches!(
+        set,
+        Err(InfernoError::Packets(PacketsError::NestingTooDeep { .. }))
+    ));
+    assert_eq!(
+        single(state.get("test".into(), vec![]).await),
+        ValueType::None
+    );
+
+    // the steps of a path count towards the limit, the key stays readable
+    state
+        .set("test".into(), vec![], nested(MAX_NESTING_DEPTH))
+        .await
+        .unwrap();
+    let set = state
+        .set(
+            "test".into(),
+            vec![Instruction::Index(0); MAX_NESTING_DEPTH - 1],
+            nested(2),
+        )
+        .await;
+    assert!(matches!(
+        set,
+        Err(InfernoError::Packets(PacketsError::NestingTooDeep { .. }))
+    ));
+    let get = state.get("test".into(), vec![]).await;
+    assert_eq!(single(get), nested(MAX_NESTING_DEPTH));
+}
+
+#[tokio::test]
+async fn test_deep_path_rejected() {
+    let state = state::State::default();
+    let path = (0..100)
+        .map(|step| Instruction::Hash(step.to_string()))
+        .collect();
+    let set = state.set("test".into(), path, ValueType::Int(1)).await;
+    assert!(matches!(
+        set,
+        Err(InfernoError::Packets(PacketsError::NestingTooDeep { .. }))
+    ));
+    assert_eq!(
+        single(state.get("test".into(), vec![]).await),
+        ValueType::None
+    );
+
+    // fields and list items already sit one level down
+    let hset = state
+        .hset("test".into(), "field".into(), nested(MAX_NESTING_DEPTH))
+        .await;
+    assert!(hset.is_err());
+    let push = state.lrpush("test".into(), nested(MAX_NESTING_DEPTH)).await;
+    assert!(push.is_err());
+    state
+        .hset("test".into(), "field".into(), nested(MAX_NESTING_DEPTH - 1))
+        .await
+        .unwrap();
+    let get = state.get("test".into(), vec![]).await;
+    assert!(matches!(single(get), ValueType::Map(_)));
+}
@@ -0,0 +1,159 @@
+use errors::{InfernoError, StateError};
+use packets::instruction::Instruction;
+use packets::value::ValueType;
+use packets::{ClientCommandExecutor, ServerResponse};
+use server::state;
+
+fn single(response: errors::Result<ServerResponse>) -> ValueType {
+    match response {
+        Ok(ServerResponse::Single { value }) => value,
+        other => panic!("Expected single response, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_hash_path() {
+    let state = state::State::default();
+    let path = || vec![Instruction::hash("field")];
+    state
+        .set("test".into(), path(), ValueType::Int(1))
+        .await
+        .unwrap();
+
+    let value = state.get("test".into(), path()).await;
+    assert_eq!(single(value), ValueType::Int(1));
+    let hget = state.hget("test".into(), "field".into()).await;
+    assert_eq!(single(hget), ValueType::Int(1));
+
+    let missing = state
+        .get("test".into(), vec![Instruction::hash("missing")])
+        .await;
+    assert_eq!(single(missing), ValueType::None);
+
+    let removed = state.del("test".into(), path()).await;
+    assert_eq!(single(removed), ValueType::Int(1));
+    let gone = state.get("test".into(), vec![]).await;
+    assert_eq!(single(gone), ValueType::None);
+}
+
+#[tokio::test]
+async fn test_list_path() {
+    let state = state::State::default();
+    for value in 0..3 {
+        state
+            .lrpush("test".into(), ValueType::Int(value))
+            .await
+            .unwrap();
+    }
+
+    state
+        .set(
+            "test".into(),
+            vec![Instruction::index(1)],
+            ValueType::Int(10),
+        )
+        .await
+        .unwrap();
+    let value = state.get("test".into(), vec![Instruction::index(1)]).await;
+    assert_eq!(single(value), ValueType::Int(10));
+
+    let out_of_range = state
+        .set(
+            "test".into(),
+            vec![Instruction::index(3)],
+            ValueType::Int(1),
+        )
+        .await;
+    assert!(matches!(
+        out_of_range,
+        Err(InfernoError::State(StateError::IndexOutOfRange))
+    ));
+
+    let removed = state.del("test".into(), vec![Instruction::index(0)]).await;
+    assert_eq!(single(removed), ValueType::Int(1));
+    let Ok(ServerResponse::Bulk { values }) = state.lrange("test".into(), 0, 10).await else {
+        panic!("Expected bulk response");
+    };
+    assert_eq!(values, vec![ValueType::Int(10), ValueType::Int(2)]);
+}
+
+#[tokio::test]
+async fn test_set_member_path() {
+    let state = state::State::default();
+    state.sadd("test".into(), vec!["a".into()]).await.unwrap();
+
+    let present = state
+        .get("test".into(), vec![Instruction::member("a")])
+        .await;
+    assert_eq!(single(present), ValueType::String("a".into()));
+
+    let write = state
+        .set(
+            "test".into(),
+            vec![Instruction::member("b")],
+            ValueType::Int(1),
+        )
+        .await;
+    assert!(matches!(
+        write,
+        Err(InfernoError::State(StateError::InvalidPath))
+    ));
+
+    let removed = state
+        .del("test".into(), vec![Instruction::member("a")])
+        .await;
+    assert_eq!(single(removed), ValueType::Int(1));
+    let gone = state.smembers("test".into()).await;
+    assert!(matches!(gone, Ok(ServerResponse::Bulk { values }) if values.is_empty()));
+}
+
+#[tokio::test]
+async fn test_failed_path_write_leaves_no_key() {
+    let state = state::State::default();
+    let write = state
+        .set(
+            "test".into(),
+            vec![Instruction::index(0)],
+            ValueType::Int(1),
+        )
+        .await;
+    assert!(write.is_err());
+    let value = state.get("test".into(), vec![]).await;
+    assert_eq!(single(value), ValueType::None);
+}
+
+#[tokio::test]
+async fn test_path_bad_key_type() {
+    let state = state::State::default();
+    state
+        .set("test".into(), vec![], ValueType::Int(1))
+        .await
+        .unwrap();
+
+    let read = state
+        .get("test".into(), vec![Instruction::hash("field")])
+        .await;
+    assert!(matches!(
+        read,
+        Err(InfernoError::State(StateError::BadKeyType))
+    ));
+
+    state
+        .set(
+            "hash".into(),
+            vec![Instruction::hash("field")],
+            ValueType::Int(1),
+        )
+        .await
+        .unwrap();
+    let too_deep = state
+        .get(
+            "hash".into(),
+            vec![Instruction::hash("field"), Instruction::hash("nested")],
+        )
+        .await;
+    assert!(matches!(
+        too_deep,
+        Err(InfernoError::State(StateError::BadKeyType))
+    ));
+}
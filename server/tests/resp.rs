@@ -0,0 +1,217 @@
+use server::{resp, state};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+use tokio::task::JoinHandle;
+
+fn serve() -> (DuplexStream, JoinHandle<errors::Result<()>>) {
+    let (client, server) = tokio::io::duplex(64 * 1024);
+    let handle = tokio::spawn(resp::serve(state::State::default(), server));
+    (client, handle)
+}
+
+// sends `request` and checks the reply byte for byte
+async fn exchange(stream: &mut DuplexStream, request: &[u8], expected: &[u8]) {
+    stream.write_all(request).await.unwrap();
+    let mut reply = vec![0u8; expected.len()];
+    stream.read_exact(&mut reply).await.unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&reply),
+        String::from_utf8_lossy(expected),
+        "reply to {:?}",
+        String::from_utf8_lossy(request)
+    );
+}
+
+#[tokio::test]
+async fn test_set_get() {
+    let (mut stream, _server) = serve();
+    exchange(
+        &mut stream,
+        b"*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$5\r\nvalue\r\n",
+        b"+OK\r\n",
+    )
+    .await;
+    exchange(
+        &mut stream,
+        b"*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n",
+        b"$5\r\nvalue\r\n",
+    )
+    .await;
+    exchange(
+        &mut stream,
+        b"*2\r\n$3\r\nGET\r\n$7\r\nmissing\r\n",
+        b"$-1\r\n",
+    )
+    .await;
+    exchange(
+        &mut stream,
+        b"*4\r\n$3\r\nSET\r\n$3\r\nkey\r\n$5\r\nother\r\n$2\r\nNX\r\n",
+        b"$-1\r\n",
+    )
+    .await;
+    exchange(
+        &mut stream,
+        b"*3\r\n$3\r\nDEL\r\n$3\r\nkey\r\n$7\r\nmissing\r\n",
+        b":1\r\n",
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_binary_value() {
+    let (mut stream, _server) = serve();
+    exchange(
+        &mut stream,
+        b"*3\r\n$3\r\nset\r\n$2\r\n\xff\x00\r\n$2\r\n\xfe\x01\r\n",
+        b"+OK\r\n",
+    )
+    .await;
+    exchange(
+        &mut stream,
+        b"*2\r\n$3\r\nget\r\n$2\r\n\xff\x00\r\n",
+        b"$2\r\n\xfe\x01\r\n",
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_hash_and_list() {
+    let (mut stream, _server) = serve();
+    exchange(
+        &mut stream,
+        b"*6\r\n$4\r\nHSET\r\n$1\r\nh\r\n$1\r\na\r\n$1\r\n1\r\n$1\r\nb\r\n$1\r\n2\r\n",
+        b":2\r\n",
+    )
+    .await;
+    exchange(
+        &mut stream,
+        b"*3\r\n$4\r\nHGET\r\n$1\r\nh\r\n$1\r\nb\r\n",
+        b"$1\r\n2\r\n",
+    )
+    .await;
+
+    exchange(
+        &mut stream,
+        b"*4\r\n$5\r\nLPUSH\r\n$1\r\nl\r\n$1\r\na\r\n$1\r\nb\r\n",
+        b":2\r\n",
+    )
+    .await;
+    exchange(
+        &mut stream,
+        b"*4\r\n$6\r\nLRANGE\r\n$1\r\nl\r\n$1\r\n0\r\n$2\r\n-1\r\n",
+        b"*2\r\n$1\r\nb\r\n$1\r\na\r\n",
+    )
+    .await;
+    exchange(&mut stream, b"RPUSH l c\r\n", b":3\r\n").await;
+    exchange(
+        &mut stream,
+        b"*2\r\n$4\r\nRPOP\r\n$1\r\nl\r\n",
+        b"$1\r\nc\r\n",
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_ttl() {
+    let (mut stream, _server) = serve();
+    exchange(&mut stream, b"TTL key\r\n", b":-2\r\n").await;
+    exchange(&mut stream, b"SET key value\r\n", b"+OK\r\n").await;
+    exchange(&mut stream, b"TTL key\r\n", b":-1\r\n").await;
+    exchange(&mut stream, b"EXPIRE key 100\r\n", b":1\r\n").await;
+    exchange(&mut stream, b"TTL key\r\n", b":100\r\n").await;
+}
+
+#[tokio::test]
+async fn test_incr_and_errors() {
+    let (mut stream, _server) = serve();
+    exchange(&mut stream, b"INCR counter\r\n", b":1\r\n").await;
+    exchange(&mut stream, b"INCRBY counter 41\r\n", b":42\r\n").await;
+    exchange(
+        &mut stream,
+        b"INCRBY counter many\r\n",
+        b"-ERR value is not an integer or out of range\r\n",
+    )
+    .await;
+    exchange(
+        &mut stream,
+        b"GET\r\n",
+        b"-ERR wrong number of arguments for 'get' command\r\n",
+    )
+    .await;
+    exchange(
+        &mut stream,
+        b"FLUSHALL\r\n",
+        b"-ERR unknown command 'flushall'\r\n",
+    )
+    .await;
+    exchange(
+        &mut stream,
+        b"SADD counter member\r\n",
+        b"-ERR Attempted to index a key with a bad type.\r\n",
+    )
+    .await;
+    exchange(&mut stream, b"PING\r\n", b"+PONG\r\n").await;
+}
+
+#[tokio::test]
+async fn test_resp3_hello() {
+    let (mut stream, _server) = serve();
+    exchange(&mut stream, b"ZADD z 1.5 a\r\n", b":1\r\n").await;
+    exchange(&mut stream, b"ZSCORE z a\r\n", b"$3\r\n1.5\r\n").await;
+
+    stream
+        .write_all(b"*2\r\n$5\r\nHELLO\r\n$1\r\n3\r\n")
+        .await
+        .unwrap();
+    let mut header = [0u8; 4];
+    stream.read_exact(&mut header).await.unwrap();
+    assert_eq!(&header, b"%6\r\n");
+    // skip the rest of the server description
+    let mut rest = vec![0u8; 256];
+    let _ =
+        tokio::time::timeout(std::time::Duration::from_millis(50), stream.read(&mut rest)).await;
+
+    exchange(&mut stream, b"ZSCORE z a\r\n", b",1.5\r\n").await;
+    exchange(&mut stream, b"GET missing\r\n", b"_\r\n").await;
+}
+
+#[tokio::test]
+async fn test_malformed_request() {
+    let (mut stream, server) = serve();
+    exchange(
+        &mut stream,
+        b"*1\r\n+PING\r\n",
+        b"-ERR Protocol error: Malformed RESP request: expected bulk string\r\n",
+    )
+    .await;
+    drop(stream);
+    assert!(server.await.unwrap().is_err());
+}
+
+#[tokio::test]
+async fn test_oversized_array() {
+    let (mut stream, server) = serve();
+    stream.write_all(b"*4294967295\r\n").await.unwrap();
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply).await.unwrap();
+    assert!(reply.starts_with(b"-ERR Protocol error: Length 4294967295 exceeds"));
+    assert!(server.await.unwrap().is_err());
+}
+
+#[tokio::test]
+async fn test_multi_argument_commands() {
+    let (mut stream, _server) = serve();
+    exchange(&mut stream, b"ZADD z 1 a 2 b\r\n", b":2\r\n").await;
+    exchange(&mut stream, b"ZADD z 3 a 4 c\r\n", b":1\r\n").await;
+    exchange(&mut stream, b"ZREM z a b missing\r\n", b":2\r\n").await;
+    exchange(&mut stream, b"ZCARD z\r\n", b":1\r\n").await;
+
+    // a key of the wrong type fails the whole command
+    exchange(&mut stream, b"SET s v\r\n", b"+OK\r\n").await;
+    exchange(
+        &mut stream,
+        b"HSET s a 1 b 2\r\n",
+        b"-ERR Attempted to index a key with a bad type.\r\n",
+    )
+    .await;
+    exchange(&mut stream, b"DEL s z missing\r\n", b":2\r\n").await;
+}
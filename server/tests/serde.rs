@@ -0,0 +1,136 @@
+mod common;
+
+use common::connect;
+use driver::prelude::*;
+use errors::{InfernoError, PacketsError};
+use packets::serde_value;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum Role {
+    Admin,
+    Member { since: u32 },
+    Guest(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct User {
+    name: String,
+    age: u8,
+    score: f64,
+    active: bool,
+    email: Option<String>,
+    tags: Vec<String>,
+    role: Role,
+    limits: BTreeMap<String, i64>,
+}
+
+fn user() -> User {
+    User {
+        name: "ada".to_string(),
+        age: 36,
+        score: 9.5,
+        active: true,
+        email: None,
+        tags: vec!["math".to_string(), "engines".to_string()],
+        role: Role::Member { since: 1843 },
+        limits: BTreeMap::from([("requests".to_string(), 100)]),
+    }
+}
+
+#[test]
+fn test_value_round_trip() {
+    let user = user();
+    let value = serde_value::to_value(&user).unwrap();
+    assert!(matches!(&value, ValueType::Map(fields) if fields.len() == 8));
+    assert_eq!(serde_value::from_value::<User>(value).unwrap(), user);
+
+    for role in [Role::Admin, Role::Guest("bob".to_string())] {
+        let value = serde_value::to_value(&role).unwrap();
+        assert_eq!(serde_value::from_value::<Role>(value).unwrap(), role);
+    }
+    assert_eq!(
+        serde_value::to_value(&Role::Admin).unwrap(),
+        ValueType::String("Admin".to_string())
+    );
+}
+
+#[test]
+fn test_map_keys_round_trip() {
+    let ids = HashMap::from([(1u32, "one".to_string()), (2, "two".to_string())]);
+    let value = serde_value::to_value(&ids).unwrap();
+    assert_eq!(
+        serde_value::from_value::<HashMap<u32, String>>(value).unwrap(),
+        ids
+    );
+
+    let flags = BTreeMap::from([(true, -1i64), (false, 0)]);
+    let value = serde_value::to_value(&flags).unwrap();
+    assert_eq!(
+        serde_value::from_value::<BTreeMap<bool, i64>>(value).unwrap(),
+        flags
+    );
+
+    let text = ValueType::Map(vec![("one".to_string(), ValueType::Int(1))]);
+    assert!(matches!(
+        serde_value::from_value::<HashMap<u32, i32>>(text),
+        Err(InfernoError::Packets(PacketsError::Serde(_)))
+    ));
+}
+
+#[test]
+fn test_fields_need_map() {
+    let fields = serde_value::to_fields(&user()).unwrap();
+    assert_eq!(
+        fields[0],
+        ("name".to_string(), ValueType::String("ada".into()))
+    );
+
+    let not_map = serde_value::to_fields(&vec![1, 2]);
+    assert!(matches!(
+        not_map,
+        Err(InfernoError::Packets(PacketsError::UnexpectedType {
+            expected: "map",
+            found: "list"
+        }))
+    ));
+
+    let wrong = serde_value::from_fields::<User>(vec![("name".to_string(), ValueType::Int(1))]);
+    assert!(matches!(
+        wrong,
+        Err(InfernoError::Packets(PacketsError::Serde(_)))
+    ));
+}
+
+#[tokio::test]
+async fn test_hash_round_trip() {
+    let mut client = connect().await;
+    let user = user();
+
+    client.typed().set_hash("user:1", &user).await.unwrap();
+    let age = client.typed().hget::<u32>("user:1", "age").await.unwrap();
+    assert_eq!(age, 36);
+
+    let read = client.typed().get_hash::<User>("user:1").await.unwrap();
+    assert_eq!(read, Some(user));
+    let missing = client.typed().get_hash::<User>("user:2").await.unwrap();
+    assert_eq!(missing, None);
+}
+
+#[tokio::test]
+async fn test_blob_round_trip() {
+    let mut client = connect().await;
+    let user = user();
+
+    client.typed().set_blob("user:1", &user).await.unwrap();
+    assert!(matches!(
+        client.typed().get::<ValueType>("user:1").await.unwrap(),
+        ValueType::Bytes(_)
+    ));
+
+    let read = client.typed().get_blob::<User>("user:1").await.unwrap();
+    assert_eq!(read, Some(user));
+    let missing = client.typed().get_blob::<User>("user:2").await.unwrap();
+    assert_eq!(missing, None);
+}
@@ -0,0 +1,231 @@
+use packets::key::Key;
+use packets::value::ValueType;
+use packets::{ClientCommandExecutor, ServerResponse};
+use server::state;
+use std::collections::HashSet;
+
+fn members(response: errors::Result<ServerResponse>) -> HashSet<String> {
+    match response {
+        Ok(ServerResponse::Bulk { values }) => values
+            .into_iter()
+            .map(|value| match value {
+                ValueType::String(member) => member,
+                other => panic!("Expected string member, got {:?}", other),
+            })
+            .collect(),
+        other => panic!("Expected bulk response, got {:?}", other),
+    }
+}
+
+fn set_of(names: &[&str]) -> HashSet<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+fn int(response: errors::Result<ServerResponse>) -> i32 {
+    match response {
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(int),
+        }) => int,
+        other => panic!("Expected int response, got {:?}", other),
+    }
+}
+
+async fn with_sets() -> state::State {
+    let state = state::State::default();
+    state
+        .clone()
+        .sadd("first".into(), vec!["a".into(), "b".into(), "c".into()])
+        .await
+        .unwrap();
+    state
+        .clone()
+        .sadd("second".into(), vec!["b".into(), "c".into(), "d".into()])
+        .await
+        .unwrap();
+    state
+}
+
+#[tokio::test]
+async fn test_sadd_smember() {
+    let state = state::State::default();
+    let added = state
+        .clone()
+        .sadd("test".into(), vec!["a".into(), "b".into(), "a".into()])
+        .await;
+    assert_eq!(int(added), 2);
+
+    let again = state
+        .clone()
+        .sadd_nx("test".into(), vec!["b".into(), "c".into()])
+        .await;
+    assert_eq!(int(again), 1);
+
+    assert_eq!(
+        int(state.clone().smember("test".into(), "c".into()).await),
+        1
+    );
+    assert_eq!(
+        int(state.clone().smember("test".into(), "d".into()).await),
+        0
+    );
+    assert_eq!(int(state.clone().scard("test".into()).await), 3);
+    assert_eq!(
+        members(state.smembers("test".into()).await),
+        set_of(&["a", "b", "c"])
+    );
+}
+
+#[tokio::test]
+async fn test_srem_removes_empty_set() {
+    let state = with_sets().await;
+    let removed = state
+        .clone()
+        .srem("first".into(), vec!["a".into(), "x".into()])
+        .await;
+    assert_eq!(int(removed), 1);
+
+    state
+        .clone()
+        .srem("first".into(), vec!["b".into(), "c".into()])
+        .await
+        .unwrap();
+    let removed = state.get("first".into(), vec![]).await;
+    assert!(matches!(
+        removed,
+        Ok(ServerResponse::Single {
+            value: ValueType::None
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_spop_srand_member() {
+    let state = with_sets().await;
+    let random = members(state.clone().srand_member("first".into(), 2).await);
+    assert_eq!(random.len(), 2);
+    assert!(random.is_subset(&set_of(&["a", "b", "c"])));
+    assert_eq!(int(state.clone().scard("first".into()).await), 3);
+
+    let popped = members(state.clone().spop("first".into(), 2).await);
+    assert_eq!(popped.len(), 2);
+    let rest = members(state.clone().smembers("first".into()).await);
+    assert_eq!(rest.len(), 1);
+    assert!(popped.is_disjoint(&rest));
+
+    let last = members(state.clone().spop("first".into(), 5).await);
+    assert_eq!(last, rest);
+    assert_eq!(int(state.scard("first".into()).await), 0);
+}
+
+#[tokio::test]
+async fn test_smove() {
+    let state = with_sets().await;
+    let moved = state
+        .clone()
+        .smove("first".into(), "second".into(), "a".into())
+        .await;
+    assert_eq!(int(moved), 1);
+
+    let missing = state
+        .clone()
+        .smove("first".into(), "second".into(), "a".into())
+        .await;
+    assert_eq!(int(missing), 0);
+
+    assert_eq!(
+        members(state.clone().smembers("first".into()).await),
+        set_of(&["b", "c"])
+    );
+    assert_eq!(
+        members(state.clone().smembers("second".into()).await),
+        set_of(&["a", "b", "c", "d"])
+    );
+
+    state
+        .clone()
+        .set("value".into(), vec![], ValueType::Int(1))
+        .await
+        .unwrap();
+    let bad = state
+        .clone()
+        .smove("first".into(), "value".into(), "b".into())
+        .await;
+    assert!(bad.is_err());
+    assert_eq!(int(state.smember("first".into(), "b".into()).await), 1);
+}
+
+#[tokio::test]
+async fn test_set_algebra() {
+    let state = with_sets().await;
+    let keys = || {
+        vec![
+            Key::from("first"),
+            Key::from("second"),
+            Key::from("missing"),
+        ]
+    };
+    assert_eq!(
+        members(state.clone().sunion(keys()).await),
+        set_of(&["a", "b", "c", "d"])
+    );
+    assert!(members(state.clone().sinter(keys()).await).is_empty());
+    assert_eq!(
+        members(
+            state
+                .clone()
+                .sinter(vec!["first".into(), "second".into()])
+                .await
+        ),
+        set_of(&["b", "c"])
+    );
+    assert_eq!(members(state.sdiff(keys()).await), set_of(&["a"]));
+}
+
+#[tokio::test]
+async fn test_set_algebra_store() {
+    let state = with_sets().await;
+    let keys = || vec![Key::from("first"), Key::from("second")];
+    assert_eq!(
+        int(state.clone().sunion_store("dest".into(), keys()).await),
+        4
+    );
+    assert_eq!(
+        int(state.clone().sinter_store("dest".into(), keys()).await),
+        2
+    );
+    assert_eq!(
+        members(state.clone().smembers("dest".into()).await),
+        set_of(&["b", "c"])
+    );
+
+    // an empty result deletes the destination
+    state
+        .clone()
+        .set("value".into(), vec![], ValueType::Int(1))
+        .await
+        .unwrap();
+    let empty = state
+        .clone()
+        .sdiff_store("value".into(), vec!["first".into(), "first".into()])
+        .await;
+    assert_eq!(int(empty), 0);
+    let removed = state.get("value".into(), vec![]).await;
+    assert!(matches!(
+        removed,
+        Ok(ServerResponse::Single {
+            value: ValueType::None
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_set_algebra_wrong_type() {
+    let state = with_sets().await;
+    state
+        .clone()
+        .set("value".into(), vec![], ValueType::Int(1))
+        .await
+        .unwrap();
+    let union = state.sunion(vec!["first".into(), "value".into()]).await;
+    assert!(union.is_err());
+}
@@ -0,0 +1,285 @@
+use errors::{InfernoError, StateError};
+use packets::score::Score;
+use packets::value::ValueType;
+use packets::{ClientCommandExecutor, ServerResponse};
+use server::state;
+
+fn bulk(response: errors::Result<ServerResponse>) -> Vec<ValueType> {
+    match response {
+        Ok(ServerResponse::Bulk { values }) => values,
+        other => panic!("Expected bulk response, got {:?}", other),
+    }
+}
+
+fn members(names: &[&str]) -> Vec<ValueType> {
+    names
+        .iter()
+        .map(|name| ValueType::String(name.to_string()))
+        .collect()
+}
+
+async fn with_members() -> state::State {
+    let state = state::State::default();
+    for (member, score) in [("b", 2), ("a", 1), ("c", 3), ("d", -4)] {
+        state
+            .clone()
+            .zadd("test".into(), score.into(), member.into())
+            .await
+            .unwrap();
+    }
+    state
+}
+
+#[tokio::test]
+async fn test_zadd_zscore() {
+    let state = state::State::default();
+    let first = state
+        .clone()
+        .zadd("test".into(), 1.into(), "a".into())
+        .await;
+    assert!(matches!(
+        first,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1)
+        })
+    ));
+
+    let second = state
+        .clone()
+        .zadd("test".into(), 5.into(), "a".into())
+        .await;
+    assert!(matches!(
+        second,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(0)
+        })
+    ));
+
+    let nx = state
+        .clone()
+        .zadd_nx("test".into(), 9.into(), "a".into())
+        .await;
+    assert!(matches!(
+        nx,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(0)
+        })
+    ));
+
+    let score = state.clone().zscore("test".into(), "a".into()).await;
+    assert!(matches!(
+        score,
+        Ok(ServerResponse::OptScore { value: Some(score) }) if score == 5.into()
+    ));
+
+    let missing = state.zscore("test".into(), "b".into()).await;
+    assert!(matches!(
+        missing,
+        Ok(ServerResponse::OptScore { value: None })
+    ));
+}
+
+#[tokio::test]
+async fn test_zmscore() {
+    let state = with_members().await;
+    let scores = state
+        .zmscore("test".into(), vec!["a".into(), "x".into(), "d".into()])
+        .await;
+    assert!(matches!(
+        scores,
+        Ok(ServerResponse::Scores { values }) if values == vec![Some(1.into()), None, Some((-4).into())]
+    ));
+}
+
+#[tokio::test]
+async fn test_zincr_by() {
+    let state = state::State::default();
+    let created = state
+        .clone()
+        .zincr_by("test".into(), Score::new(2.5).unwrap(), "a".into())
+        .await;
+    assert!(matches!(
+        created,
+        Ok(ServerResponse::OptScore { value: Some(score) }) if score.get() == 2.5
+    ));
+
+    let decr = state
+        .clone()
+        .zdecr_by("test".into(), 5.into(), "a".into())
+        .await;
+    assert!(matches!(
+        decr,
+        Ok(ServerResponse::OptScore { value: Some(score) }) if score.get() == -2.5
+    ));
+
+    state
+        .clone()
+        .zadd("test".into(), Score::MAX, "b".into())
+        .await
+        .unwrap();
+    let nan = state.zdecr_by("test".into(), Score::MAX, "b".into()).await;
+    assert!(matches!(
+        nan,
+        Err(InfernoError::State(StateError::InvalidScore))
+    ));
+}
+
+#[tokio::test]
+async fn test_precise_scores() {
+    let state = state::State::default();
+    let precise = Score::new(0.1 + 0.2).unwrap();
+    state
+        .clone()
+        .zadd("test".into(), precise, "a".into())
+        .await
+        .unwrap();
+    state
+        .clone()
+        .zadd("test".into(), Score::MIN, "b".into())
+        .await
+        .unwrap();
+
+    let score = state.clone().zscore("test".into(), "a".into()).await;
+    assert!(matches!(
+        score,
+        Ok(ServerResponse::OptScore { value: Some(score) }) if score.get() == 0.1 + 0.2
+    ));
+
+    let range = bulk(
+        state
+            .zrange_by_score("test".into(), Score::MIN, 0.into())
+            .await,
+    );
+    assert_eq!(range, members(&["b"]));
+}
+
+#[tokio::test]
+async fn test_zrange() {
+    let state = with_members().await;
+    let all = bulk(state.clone().zrange("test".into(), 0, -1).await);
+    assert_eq!(all, members(&["d", "a", "b", "c"]));
+
+    let tail = bulk(state.clone().zrange("test".into(), -2, 100).await);
+    assert_eq!(tail, members(&["b", "c"]));
+
+    let empty = bulk(state.clone().zrange("test".into(), 3, 1).await);
+    assert!(empty.is_empty());
+
+    let missing = bulk(state.zrange("missing".into(), 0, -1).await);
+    assert!(missing.is_empty());
+}
+
+#[tokio::test]
+async fn test_zrange_by_score_zcount() {
+    let state = with_members().await;
+    let range = bulk(
+        state
+            .clone()
+            .zrange_by_score("test".into(), 0.into(), 2.into())
+            .await,
+    );
+    assert_eq!(range, members(&["a", "b"]));
+
+    let count = state.zcount("test".into(), (-10).into(), 2.into()).await;
+    assert!(matches!(
+        count,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(3)
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_zrank_zcard() {
+    let state = with_members().await;
+    let rank = state.clone().zrank("test".into(), "b".into()).await;
+    assert!(matches!(
+        rank,
+        Ok(ServerResponse::OptInt { value: Some(2) })
+    ));
+
+    let missing = state.clone().zrank("test".into(), "x".into()).await;
+    assert!(matches!(
+        missing,
+        Ok(ServerResponse::OptInt { value: None })
+    ));
+
+    let card = state.zcard("test".into()).await;
+    assert!(matches!(
+        card,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(4)
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_zpop() {
+    let state = with_members().await;
+    let min = state.clone().zpop_min("test".into(), 1).await;
+    assert!(matches!(
+        min,
+        Ok(ServerResponse::ScoredBulk { values }) if values == vec![("d".to_string(), (-4).into())]
+    ));
+
+    let max = state.clone().zpop_max("test".into(), 2).await;
+    assert!(matches!(
+        max,
+        Ok(ServerResponse::ScoredBulk { values })
+            if values == vec![("c".to_string(), 3.into()), ("b".to_string(), 2.into())]
+    ));
+
+    state.clone().zpop_min("test".into(), 5).await.unwrap();
+    let removed = state.get("test".into(), vec![]).await;
+    assert!(matches!(
+        removed,
+        Ok(ServerResponse::Single {
+            value: ValueType::None
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_zrem_removes_empty_set() {
+    let state = state::State::default();
+    state
+        .clone()
+        .zadd("test".into(), 1.into(), "a".into())
+        .await
+        .unwrap();
+    let removed = state.clone().zrem("test".into(), "a".into()).await;
+    assert!(matches!(
+        removed,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1)
+        })
+    ));
+
+    let again = state.clone().zrem("missing".into(), "a".into()).await;
+    assert!(matches!(
+        again,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(0)
+        })
+    ));
+
+    let removed = state.get("test".into(), vec![]).await;
+    assert!(matches!(
+        removed,
+        Ok(ServerResponse::Single {
+            value: ValueType::None
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_zcard_wrong_type() {
+    let state = state::State::default();
+    state
+        .clone()
+        .set("test".into(), vec![], ValueType::Int(1))
+        .await
+        .unwrap();
+    let card = state.zcard("test".into()).await;
+    assert!(card.is_err());
+}
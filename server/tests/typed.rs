@@ -0,0 +1,128 @@
+mod common;
+
+use common::connect;
+use driver::prelude::*;
+use errors::{InfernoError, PacketsError};
+use std::collections::{BTreeSet, HashMap};
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_typed_values() {
+    let mut client = connect().await;
+
+    client.typed().set("name", "inferno").await.unwrap();
+    let name = client.typed().get::<String>("name").await.unwrap();
+    assert_eq!(name, "inferno");
+    let missing = client.typed().get::<Option<String>>("missing").await;
+    assert_eq!(missing.unwrap(), None);
+
+    client.typed().set("count", 41).await.unwrap();
+    assert_eq!(client.typed().incr("count").await.unwrap(), 42);
+    assert_eq!(client.typed().get::<u32>("count").await.unwrap(), 42);
+
+    client.typed().set("blob", vec![0u8, 0xff]).await.unwrap();
+    let blob = client.typed().get::<Vec<u8>>("blob").await.unwrap();
+    assert_eq!(blob, [0, 0xff]);
+
+    client.typed().set("tags", vec!["a", "b"]).await.unwrap();
+    let tags = client.typed().get::<Vec<String>>("tags").await.unwrap();
+    assert_eq!(tags, ["a", "b"]);
+
+    let values = client
+        .typed()
+        .mget::<Vec<Option<i64>>, _>(["count", "missing"])
+        .await
+        .unwrap();
+    assert_eq!(values, [Some(42), None]);
+}
+
+#[tokio::test]
+async fn test_typed_ttl() {
+    let mut client = connect().await;
+    client.typed().set("key", true).await.unwrap();
+    assert_eq!(client.typed().ttl("key").await.unwrap(), None);
+
+    assert!(client
+        .typed()
+        .expire("key", Duration::from_secs(100))
+        .await
+        .unwrap());
+    assert_eq!(
+        client.typed().ttl("key").await.unwrap(),
+        Some(Duration::from_secs(100))
+    );
+    assert!(client.typed().persist("key").await.unwrap());
+    assert!(client.typed().get::<bool>("key").await.unwrap());
+
+    // partial seconds round up rather than expiring the key at once
+    client
+        .typed()
+        .expire("key", Duration::from_millis(500))
+        .await
+        .unwrap();
+    assert_eq!(
+        client.typed().ttl("key").await.unwrap(),
+        Some(Duration::from_secs(1))
+    );
+    assert!(client.typed().get::<bool>("key").await.unwrap());
+}
+
+#[tokio::test]
+async fn test_typed_collections() {
+    let mut client = connect().await;
+
+    let scores = HashMap::from([("alice".to_string(), 3i64), ("bob".to_string(), 5)]);
+    client.typed().hmset("scores", &scores).await.unwrap();
+    let read = client
+        .typed()
+        .hget_all::<HashMap<String, i64>>("scores")
+        .await
+        .unwrap();
+    assert_eq!(read, scores);
+    assert_eq!(
+        client.typed().hincr_by("scores", "bob", 2).await.unwrap(),
+        7
+    );
+    assert_eq!(client.typed().hlen("scores").await.unwrap(), 2);
+
+    client.typed().sadd("set", ["b", "a", "b"]).await.unwrap();
+    let members = client
+        .typed()
+        .smembers::<BTreeSet<String>>("set")
+        .await
+        .unwrap();
+    assert_eq!(members, BTreeSet::from(["a".to_string(), "b".to_string()]));
+
+    client.typed().zadd("ranks", 1.5, "first").await.unwrap();
+    assert_eq!(
+        client.typed().zscore("ranks", "first").await.unwrap(),
+        Some(1.5)
+    );
+    assert_eq!(client.typed().zscore("ranks", "none").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_typed_mismatch() {
+    let mut client = connect().await;
+    client.typed().set("name", "inferno").await.unwrap();
+
+    let number = client.typed().get::<i64>("name").await;
+    assert!(matches!(
+        number,
+        Err(InfernoError::Packets(PacketsError::UnexpectedType {
+            expected: "i64",
+            found: "string"
+        }))
+    ));
+
+    client.typed().set("count", -1).await.unwrap();
+    let unsigned = client.typed().get::<u32>("count").await;
+    assert!(unsigned.is_err());
+}
+
+#[tokio::test]
+async fn test_typed_senders() {
+    let client = connect().await.into_ref();
+    (&client).typed().set("key", 1).await.unwrap();
+    assert_eq!((&client).typed().get::<i32>("key").await.unwrap(), 1);
+}
@@ -1,5 +1,7 @@
+use errors::{InfernoError, StateError};
+use packets::key::Key;
 use packets::value::ValueType;
-use packets::{ClientCommandExecutor, ServerResponse};
+use packets::{ClientCommand, ClientCommandExecutor, Packet, ServerResponse};
 use server::state;
 
 #[tokio::test]
@@ -13,3 +15,413 @@ async fn test_empty_incr() {
         })
     ));
 }
+
+#[tokio::test]
+async fn test_incr_by_empty() {
+    let state = state::State::default();
+    let incr_result = state.incr_by("test".into(), 5).await;
+    assert!(matches!(
+        incr_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(5)
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_get_missing() {
+    let state = state::State::default();
+    let get_result = state.get("test".into(), vec![]).await;
+    assert!(matches!(
+        get_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::None
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_set_get() {
+    let state = state::State::default();
+    let set_result = state
+        .set("test".into(), vec![], ValueType::String("value".into()))
+        .await;
+    assert!(matches!(set_result, Ok(ServerResponse::Ok)));
+
+    let get_result = state.get("test".into(), vec![]).await;
+    assert!(matches!(
+        get_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::String(value)
+        }) if value == "value"
+    ));
+}
+
+#[tokio::test]
+async fn test_set_nx() {
+    let state = state::State::default();
+    let first = state.set_nx("test".into(), ValueType::Int(1)).await;
+    assert!(matches!(
+        first,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1)
+        })
+    ));
+
+    let second = state.set_nx("test".into(), ValueType::Int(2)).await;
+    assert!(matches!(
+        second,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(0)
+        })
+    ));
+
+    let get_result = state.get("test".into(), vec![]).await;
+    assert!(matches!(
+        get_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1)
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_get_set() {
+    let state = state::State::default();
+    let first = state.get_set("test".into(), ValueType::Int(1)).await;
+    assert!(matches!(
+        first,
+        Ok(ServerResponse::Single {
+            value: ValueType::None
+        })
+    ));
+
+    let second = state.get_set("test".into(), ValueType::Int(2)).await;
+    assert!(matches!(
+        second,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1)
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_mset_mget() {
+    let state = state::State::default();
+    let mset_result = state
+        .mset(
+            vec!["a".into(), "b".into()],
+            vec![ValueType::Int(1), ValueType::Int(2)],
+        )
+        .await;
+    assert!(matches!(mset_result, Ok(ServerResponse::Ok)));
+
+    let mget_result = state
+        .mget(vec!["a".into(), "missing".into(), "b".into()])
+        .await;
+    let Ok(ServerResponse::Bulk { values }) = mget_result else {
+        panic!("Expected bulk response, got {:?}", mget_result);
+    };
+    assert_eq!(
+        values,
+        vec![ValueType::Int(1), ValueType::None, ValueType::Int(2)]
+    );
+}
+
+#[tokio::test]
+async fn test_mset_mismatched() {
+    let state = state::State::default();
+    let mset_result = state
+        .mset(vec!["a".into(), "b".into()], vec![ValueType::Int(1)])
+        .await;
+    assert!(matches!(
+        mset_result,
+        Err(InfernoError::State(StateError::MismatchedArguments))
+    ));
+}
+
+#[tokio::test]
+async fn test_mset_nx_all_or_nothing() {
+    let state = state::State::default();
+    state
+        .set("b".into(), vec![], ValueType::Int(0))
+        .await
+        .unwrap();
+
+    let mset_result = state
+        .mset_nx(
+            vec!["a".into(), "b".into()],
+            vec![ValueType::Int(1), ValueType::Int(2)],
+        )
+        .await;
+    assert!(matches!(
+        mset_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(0)
+        })
+    ));
+
+    let mget_result = state.mget(vec!["a".into(), "b".into()]).await;
+    let Ok(ServerResponse::Bulk { values }) = mget_result else {
+        panic!("Expected bulk response, got {:?}", mget_result);
+    };
+    assert_eq!(values, vec![ValueType::None, ValueType::Int(0)]);
+
+    let mset_result = state
+        .mset_nx(
+            vec!["a".into(), "c".into()],
+            vec![ValueType::Int(1), ValueType::Int(3)],
+        )
+        .await;
+    assert!(matches!(
+        mset_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1)
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_get_del() {
+    let state = state::State::default();
+    state
+        .set("a".into(), vec![], ValueType::Int(1))
+        .await
+        .unwrap();
+
+    let get_del_result = state.get_del(vec!["a".into(), "missing".into()]).await;
+    let Ok(ServerResponse::Bulk { values }) = get_del_result else {
+        panic!("Expected bulk response, got {:?}", get_del_result);
+    };
+    assert_eq!(values, vec![ValueType::Int(1)]);
+
+    let get_result = state.get("a".into(), vec![]).await;
+    assert!(matches!(
+        get_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::None
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_get_bad_key_type() {
+    let state = state::State::default();
+    state
+        .sadd("test".into(), vec!["member".into()])
+        .await
+        .unwrap();
+
+    let get_result = state.get("test".into(), vec![]).await;
+    assert!(matches!(
+        get_result,
+        Err(InfernoError::State(StateError::CannotReturnKeyType))
+    ));
+
+    let mget_result = state.mget(vec!["test".into()]).await;
+    assert!(matches!(
+        mget_result,
+        Err(InfernoError::State(StateError::CannotReturnKeyType))
+    ));
+}
+
+#[tokio::test]
+async fn test_incr_by_negative() {
+    let state = state::State::default();
+    state.incr_by("test".into(), 2).await.unwrap();
+    let incr_result = state.incr_by("test".into(), -5).await;
+    assert!(matches!(
+        incr_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(-3)
+        })
+    ));
+
+    let decr_result = state.decr_by("test".into(), -3).await;
+    assert!(matches!(
+        decr_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(0)
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_incr_overflow() {
+    let state = state::State::default();
+    state
+        .set("test".into(), vec![], ValueType::Int(i32::MAX))
+        .await
+        .unwrap();
+    let incr_result = state.incr("test".into()).await;
+    assert!(matches!(
+        incr_result,
+        Err(InfernoError::State(StateError::Overflow))
+    ));
+
+    state
+        .set("test".into(), vec![], ValueType::Int(i32::MIN))
+        .await
+        .unwrap();
+    let decr_result = state.decr("test".into()).await;
+    assert!(matches!(
+        decr_result,
+        Err(InfernoError::State(StateError::Overflow))
+    ));
+}
+
+#[tokio::test]
+async fn test_incr_bad_key_type() {
+    let state = state::State::default();
+    state
+        .set("test".into(), vec![], ValueType::String("value".into()))
+        .await
+        .unwrap();
+    let incr_result = state.incr("test".into()).await;
+    assert!(matches!(
+        incr_result,
+        Err(InfernoError::State(StateError::BadKeyType))
+    ));
+}
+
+#[tokio::test]
+async fn test_incr_keeps_width() {
+    let state = state::State::default();
+    let cases = [
+        (
+            ValueType::Int64(i64::from(i32::MAX)),
+            ValueType::Int64(i64::from(i32::MAX) + 1),
+        ),
+        (ValueType::UInt(1), ValueType::UInt(2)),
+        (
+            ValueType::UInt64(u64::from(u32::MAX)),
+            ValueType::UInt64(u64::from(u32::MAX) + 1),
+        ),
+    ];
+    for (initial, expected) in cases {
+        state.set("test".into(), vec![], initial).await.unwrap();
+        let incr_result = state.incr("test".into()).await;
+        assert!(matches!(
+            incr_result,
+            Ok(ServerResponse::Single { value }) if value == expected
+        ));
+    }
+}
+
+#[tokio::test]
+async fn test_incr_wide_overflow() {
+    let state = state::State::default();
+    state
+        .set("test".into(), vec![], ValueType::UInt(0))
+        .await
+        .unwrap();
+    let decr_result = state.decr("test".into()).await;
+    assert!(matches!(
+        decr_result,
+        Err(InfernoError::State(StateError::Overflow))
+    ));
+
+    state
+        .set("test".into(), vec![], ValueType::UInt64(u64::MAX))
+        .await
+        .unwrap();
+    let incr_result = state.incr_by("test".into(), 1).await;
+    assert!(matches!(
+        incr_result,
+        Err(InfernoError::State(StateError::Overflow))
+    ));
+
+    state
+        .set("test".into(), vec![], ValueType::Int64(i64::MIN))
+        .await
+        .unwrap();
+    let decr_result = state.decr_by("test".into(), i64::MIN).await;
+    assert!(matches!(
+        decr_result,
+        Err(InfernoError::State(StateError::Overflow))
+    ));
+}
+
+#[tokio::test]
+async fn test_hincr_keeps_width() {
+    let state = state::State::default();
+    state
+        .hset("test".into(), "field".into(), ValueType::UInt64(1))
+        .await
+        .unwrap();
+    let incr_result = state.hincr_by("test".into(), "field".into(), 1 << 40).await;
+    assert!(matches!(
+        incr_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::UInt64(value)
+        }) if value == (1 << 40) + 1
+    ));
+
+    state
+        .hset("test".into(), "float".into(), ValueType::Float(1.5.into()))
+        .await
+        .unwrap();
+    let float_result = state.hincr("test".into(), "float".into()).await;
+    assert!(matches!(
+        float_result,
+        Err(InfernoError::State(StateError::BadKeyType))
+    ));
+}
+
+#[tokio::test]
+async fn test_scalar_wire_round_trip() {
+    let values = vec![
+        ValueType::Int64(-1 << 40),
+        ValueType::UInt(u32::MAX),
+        ValueType::UInt64(u64::MAX),
+        ValueType::Float(f64::NAN.into()),
+        ValueType::Bool(true),
+        ValueType::Bytes(vec![0, 159, 146, 150]),
+    ];
+    let mut buffer = Vec::new();
+    values.write(&mut buffer).await.unwrap();
+    let read = Vec::<ValueType>::read(&mut buffer.as_slice())
+        .await
+        .unwrap();
+    assert_eq!(read, values);
+}
+
+#[tokio::test]
+async fn test_binary_key() {
+    let state = state::State::default();
+    let key = Key::from(vec![0xff, 0x00, 0xfe]);
+    state
+        .set(key.clone(), vec![], ValueType::Int(1))
+        .await
+        .unwrap();
+
+    let get_result = state.get("\u{ff}".into(), vec![]).await;
+    assert!(matches!(
+        get_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::None
+        })
+    ));
+
+    let mut buffer = Vec::new();
+    ClientCommand::Get {
+        key: key.clone(),
+        path: vec![],
+    }
+    .write(&mut buffer)
+    .await
+    .unwrap();
+    let command = ClientCommand::read(&mut buffer.as_slice()).await.unwrap();
+    let ClientCommand::Get { key: read, .. } = command else {
+        panic!("Expected get command, got {:?}", command);
+    };
+    assert_eq!(read, key);
+
+    let get_result = state.get(read, vec![]).await;
+    assert!(matches!(
+        get_result,
+        Ok(ServerResponse::Single {
+            value: ValueType::Int(1)
+        })
+    ));
+}